@@ -0,0 +1,472 @@
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// The years EXIF dates can hold, which always have four digits.
+pub const YEARS: std::ops::RangeInclusive<i64> = 1..=9999;
+
+/// A calendar date and wall clock time without timezone.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DateTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl DateTime {
+    /// Midnight of the given day.
+    pub fn date(year: i64, month: u32, day: u32) -> Self {
+        DateTime {
+            year,
+            month,
+            day,
+            hour: 0,
+            minute: 0,
+            second: 0,
+        }
+    }
+
+    /// Convert seconds since the unix epoch.
+    pub fn from_unix(timestamp: i64) -> Self {
+        let days = timestamp.div_euclid(SECONDS_PER_DAY);
+        let seconds = timestamp.rem_euclid(SECONDS_PER_DAY) as u32;
+        let (year, month, day) = civil_from_days(days);
+
+        DateTime {
+            year,
+            month,
+            day,
+            hour: seconds / 3600,
+            minute: seconds % 3600 / 60,
+            second: seconds % 60,
+        }
+    }
+
+    /// Seconds since the unix epoch.
+    pub fn to_unix(self) -> i64 {
+        days_from_civil(self.year, self.month, self.day) * SECONDS_PER_DAY
+            + i64::from(self.hour * 3600 + self.minute * 60 + self.second)
+    }
+
+    /// The date time the given number of seconds later, rolling over into the following days.
+    pub fn add_seconds(self, seconds: i64) -> Self {
+        DateTime::from_unix(self.to_unix() + seconds)
+    }
+
+    /// Like [`DateTime::add_seconds`], or `None` if the result doesn't fit a unix timestamp.
+    pub fn checked_add_seconds(self, seconds: i64) -> Option<Self> {
+        Some(DateTime::from_unix(self.to_unix().checked_add(seconds)?))
+    }
+
+    /// The same day with some of the time of day replaced.
+    pub fn with_time(self, hour: Option<u32>, minute: Option<u32>, second: Option<u32>) -> Self {
+        DateTime {
+            hour: hour.unwrap_or(self.hour),
+            minute: minute.unwrap_or(self.minute),
+            second: second.unwrap_or(self.second),
+            ..self
+        }
+    }
+
+    /// Whether the date can be written to EXIF, see [`YEARS`].
+    pub fn is_writable(&self) -> bool {
+        YEARS.contains(&self.year)
+    }
+
+    /// Format as a reduced precision XMP date, e.g. `1999-01` for [`Precision::Month`].
+    pub fn truncate(&self, precision: Precision) -> String {
+        match precision {
+            Precision::Year => format!("{:04}", self.year),
+            Precision::Month => format!("{:04}-{:02}", self.year, self.month),
+            Precision::Day => format!("{:04}-{:02}-{:02}", self.year, self.month, self.day),
+        }
+    }
+
+    /// Format as an XMP date, e.g. `1999-01-01T00:00:00`.
+    pub fn xmp(&self) -> String {
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// How precisely a date is known.
+#[derive(Clone, Copy)]
+pub enum Precision {
+    Year,
+    Month,
+    Day,
+}
+
+impl Precision {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "year" => Some(Precision::Year),
+            "month" => Some(Precision::Month),
+            "day" => Some(Precision::Day),
+            _ => None,
+        }
+    }
+}
+
+/// Formats as an EXIF date, e.g. `1999:01:01 00:00:00`.
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}:{:02}:{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// Parse the start date argument, either `<year>-<month>-<day>`, `@<unix timestamp>`, `now`,
+/// `today` or `yesterday`. Instants are resolved in the timezone `offset` seconds east of UTC.
+/// Years may have any number of digits but must be within [`YEARS`]. Days past the end of the
+/// month are invalid, or replaced by its last day if `clamp_day`.
+pub fn parse_start(value: &str, offset: i64, clamp_day: bool) -> Option<DateTime> {
+    parse_instant(value, offset, clamp_day).filter(DateTime::is_writable)
+}
+
+fn parse_instant(value: &str, offset: i64, clamp_day: bool) -> Option<DateTime> {
+    if let Some(timestamp) = value.strip_prefix('@') {
+        let timestamp: i64 = timestamp.parse().ok()?;
+        return Some(DateTime::from_unix(timestamp.checked_add(offset)?));
+    }
+
+    let now = || {
+        let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+        Some(DateTime::from_unix(elapsed.as_secs() as i64 + offset))
+    };
+    let midnight =
+        |date_time: DateTime| DateTime::date(date_time.year, date_time.month, date_time.day);
+
+    match value {
+        "now" => return now(),
+        "today" => return now().map(midnight),
+        "yesterday" => return now().map(|now| midnight(now.add_seconds(-SECONDS_PER_DAY))),
+        _ => {}
+    }
+
+    let mut parts = value.split(['-', ':']);
+    let year = parts.next()?.parse().ok()?;
+    let month = parts.next()?.parse().ok()?;
+    let day = parts.next()?.parse().ok()?;
+
+    if parts.next().is_some() || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let last_day = days_in_month(year, month);
+    match day {
+        day if day <= last_day => Some(DateTime::date(year, month, day)),
+        _ if clamp_day => Some(DateTime::date(year, month, last_day)),
+        _ => None,
+    }
+}
+
+/// Number of days in a month of the proleptic Gregorian calendar.
+pub fn days_in_month(year: i64, month: u32) -> u32 {
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+
+    match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Parse a time of day as `HH:MM` or `HH:MM:SS`.
+pub fn parse_time(value: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = value.split(':');
+    let hour = parse_time_part(parts.next()?, 23)?;
+    let minute = parse_time_part(parts.next()?, 59)?;
+    let second = parts
+        .next()
+        .map_or(Some(0), |second| parse_time_part(second, 59))?;
+
+    if parts.next().is_some() {
+        return None;
+    }
+
+    Some((hour, minute, second))
+}
+
+/// Parse an hour, minute or second of at most `max`.
+pub fn parse_time_part(value: &str, max: u32) -> Option<u32> {
+    value.parse().ok().filter(|part| *part <= max)
+}
+
+/// Parse a timezone as `utc` or an offset like `+02:00`, returning seconds east of UTC.
+pub fn parse_offset(value: &str) -> Option<i64> {
+    if value.eq_ignore_ascii_case("utc") || value == "Z" {
+        return Some(0);
+    }
+
+    let (sign, offset) = match value.split_at_checked(1)? {
+        ("+", offset) => (1, offset),
+        ("-", offset) => (-1, offset),
+        _ => return None,
+    };
+    let (hours, minutes) = offset
+        .split_once(':')
+        .or_else(|| offset.split_at_checked(2))?;
+    let hours: i64 = hours.parse().ok()?;
+    let minutes: i64 = minutes.parse().ok()?;
+
+    if hours > 14 || minutes > 59 || hours < 0 || minutes < 0 {
+        return None;
+    }
+
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Format an offset in seconds east of UTC like `+02:00`.
+pub fn format_offset(offset: i64) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    let minutes = offset.abs() / 60;
+
+    format!("{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+}
+
+/// Parse a positive duration like `5s`, `2m`, `1h` or `1d` into seconds. A plain number is in
+/// seconds.
+pub fn parse_duration(value: &str) -> Option<i64> {
+    let digits = value.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let unit = match &value[digits.len()..] {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => SECONDS_PER_DAY,
+        _ => return None,
+    };
+    let count: i64 = digits.parse().ok()?;
+
+    (count > 0).then_some(count.checked_mul(unit)?)
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+///
+/// See <https://howardhinnant.github.io/date_algorithms.html#days_from_civil>.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = i64::from(month);
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Proleptic Gregorian date of a number of days since 1970-01-01.
+///
+/// See <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unix_round_trip() {
+        let cases = [
+            (DateTime::date(1970, 1, 1), 0),
+            (DateTime::date(2000, 2, 29), 951_782_400),
+            (DateTime::date(2000, 3, 1), 951_868_800),
+            (DateTime::date(1900, 2, 28), -2_203_977_600),
+            (DateTime::date(1900, 3, 1), -2_203_891_200),
+            (DateTime::date(1, 1, 1), -62_135_596_800),
+            (
+                DateTime::date(9999, 12, 31).with_time(Some(23), Some(59), Some(59)),
+                253_402_300_799,
+            ),
+            (
+                DateTime::date(1969, 12, 31).with_time(Some(23), Some(59), Some(59)),
+                -1,
+            ),
+        ];
+        for (date_time, timestamp) in cases {
+            assert_eq!(date_time.to_unix(), timestamp, "{}", date_time);
+            assert!(DateTime::from_unix(timestamp) == date_time, "{}", date_time);
+        }
+    }
+
+    #[test]
+    fn checked_add_seconds_overflow() {
+        let start = DateTime::date(1999, 1, 1);
+        assert!(start.checked_add_seconds(86_400) == Some(DateTime::date(1999, 1, 2)));
+        assert!(start.checked_add_seconds(-86_400) == Some(DateTime::date(1998, 12, 31)));
+        assert!(start.checked_add_seconds(i64::MAX).is_none());
+        assert!(start.checked_add_seconds(i64::MIN).is_some());
+    }
+
+    #[test]
+    fn civil_days_round_trip_for_all_years() {
+        let first = days_from_civil(*YEARS.start(), 1, 1);
+        let last = days_from_civil(*YEARS.end(), 12, 31);
+        assert_eq!((first, last), (-719_162, 2_932_896));
+
+        let mut expected = (*YEARS.start(), 1, 1);
+        for days in first..=last {
+            assert_eq!(civil_from_days(days), expected, "{}", days);
+            assert_eq!(days_from_civil(expected.0, expected.1, expected.2), days);
+
+            let (year, month, day) = expected;
+            expected = if day < days_in_month(year, month) {
+                (year, month, day + 1)
+            } else if month < 12 {
+                (year, month + 1, 1)
+            } else {
+                (year + 1, 1, 1)
+            };
+        }
+    }
+
+    #[test]
+    fn days_in_month_of_leap_and_century_years() {
+        let cases = [
+            (2023, 2, 28),
+            (2024, 2, 29),
+            (1900, 2, 28),
+            (2000, 2, 29),
+            (1, 2, 28),
+            (4, 2, 29),
+            (9996, 2, 29),
+            (2024, 1, 31),
+            (2024, 4, 30),
+            (2024, 12, 31),
+        ];
+        for (year, month, days) in cases {
+            assert_eq!(days_in_month(year, month), days, "{}-{}", year, month);
+        }
+    }
+
+    #[test]
+    fn parse_start_dates() {
+        let cases = [
+            ("1999-01-01", 0, false, Some(DateTime::date(1999, 1, 1))),
+            ("1999:01:01", 0, false, Some(DateTime::date(1999, 1, 1))),
+            ("800-05-01", 0, false, Some(DateTime::date(800, 5, 1))),
+            ("0800-05-01", 0, false, Some(DateTime::date(800, 5, 1))),
+            ("1-01-01", 0, false, Some(DateTime::date(1, 1, 1))),
+            ("9999-12-31", 0, false, Some(DateTime::date(9999, 12, 31))),
+            ("0-12-31", 0, false, None),
+            ("10000-01-01", 0, false, None),
+            ("2024-02-29", 0, false, Some(DateTime::date(2024, 2, 29))),
+            ("2023-02-29", 0, false, None),
+            ("2023-02-31", 0, true, Some(DateTime::date(2023, 2, 28))),
+            ("1900-02-29", 0, true, Some(DateTime::date(1900, 2, 28))),
+            ("2023-13-01", 0, true, None),
+            ("2023-00-01", 0, false, None),
+            ("2023-01-32", 0, true, None),
+            ("2023-01-00", 0, false, None),
+            ("2023-01", 0, false, None),
+            ("2023-01-01-01", 0, false, None),
+            ("", 0, false, None),
+            ("@0", 0, false, Some(DateTime::date(1970, 1, 1))),
+            (
+                "@0",
+                3600,
+                false,
+                Some(DateTime::date(1970, 1, 1).with_time(Some(1), None, None)),
+            ),
+            (
+                "@-1",
+                0,
+                false,
+                Some(DateTime::date(1969, 12, 31).with_time(Some(23), Some(59), Some(59))),
+            ),
+            ("@-62135596801", 0, false, None),
+            ("@253402300800", 0, false, None),
+            ("@9223372036854775807", 1, false, None),
+            ("@x", 0, false, None),
+        ];
+        for (value, offset, clamp_day, expected) in cases {
+            assert!(
+                parse_start(value, offset, clamp_day) == expected,
+                "{:?}",
+                value
+            );
+        }
+
+        for value in ["now", "today", "yesterday"] {
+            assert!(parse_start(value, 0, false).is_some(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn parse_offsets() {
+        let cases = [
+            ("utc", Some(0)),
+            ("UTC", Some(0)),
+            ("Z", Some(0)),
+            ("+00:00", Some(0)),
+            ("+02:00", Some(7200)),
+            ("-05:30", Some(-19_800)),
+            ("+0545", Some(20_700)),
+            ("+14:00", Some(50_400)),
+            ("+15:00", None),
+            ("+02:60", None),
+            ("+02", None),
+            ("02:00", None),
+            ("+-2:00", None),
+            ("", None),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(parse_offset(value), expected, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn format_offsets_round_trip() {
+        for offset in [0, 7200, -19_800, 20_700, 50_400, -43_200] {
+            assert_eq!(parse_offset(&format_offset(offset)), Some(offset));
+        }
+    }
+
+    #[test]
+    fn parse_durations() {
+        let cases = [
+            ("5", Some(5)),
+            ("5s", Some(5)),
+            ("2m", Some(120)),
+            ("1h", Some(3600)),
+            ("1d", Some(86_400)),
+            ("0", None),
+            ("0s", None),
+            ("-5s", None),
+            ("5x", None),
+            ("5ms", None),
+            ("s", None),
+            ("", None),
+            ("9223372036854775807d", None),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(parse_duration(value), expected, "{:?}", value);
+        }
+    }
+}
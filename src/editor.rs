@@ -0,0 +1,721 @@
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::io::{self, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::datetime::{self, DateTime, Precision};
+use crate::exiftool::Exiftool;
+use crate::files;
+
+/// Reads and writes the dates of image files with a pool of exiftool processes.
+///
+/// ```no_run
+/// # async fn example() -> Result<(), exif_film::Error> {
+/// use exif_film::datetime::DateTime;
+/// use exif_film::{Changes, ExifDateEditor};
+///
+/// let editor = ExifDateEditor::new(4, None);
+/// let changes = Changes::new(DateTime::date(1999, 1, 1), "scan01.tif");
+/// editor.apply("scan01.tif", &changes, None).await?;
+/// editor.close().await;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ExifDateEditor {
+    exiftool: Exiftool,
+}
+
+impl ExifDateEditor {
+    /// An editor running at most `jobs` exiftool processes at once. Commands that take longer
+    /// than `timeout` fail with [`ErrorKind::TimedOut`].
+    pub fn new(jobs: usize, timeout: Option<Duration>) -> Self {
+        ExifDateEditor {
+            exiftool: Exiftool::new(jobs, timeout),
+        }
+    }
+
+    /// Start an exiftool process before the first file, failing if exiftool isn't installed.
+    pub async fn start(&self) -> Result<(), Error> {
+        Ok(self.exiftool.start().await?)
+    }
+
+    /// Check that a file is in a format exiftool can write and doesn't look corrupt, before
+    /// handing it to exiftool. Returns a warning for files that may be damaged but can still be
+    /// written.
+    pub async fn check(&self, file: &str) -> Result<Option<&'static str>, Error> {
+        if let Some(advice) = unsupported_format(file) {
+            return Err(Error::new(ErrorKind::Unsupported, advice));
+        }
+
+        Ok(check_image(file).await?)
+    }
+
+    /// The current value of a date tag of a file, if it has one.
+    pub async fn read_date(&self, file: &str, tag: DateTag) -> Result<DateRead, Error> {
+        let args = vec![
+            OsString::from("-s3"),
+            OsString::from(format!("-{}", tag.name())),
+            command_path(file),
+        ];
+        let output = self.exiftool.run(args).await?;
+
+        let value = output.stdout.trim().to_string();
+        Ok(DateRead {
+            value: (!value.is_empty()).then_some(value),
+            elapsed: output.elapsed,
+        })
+    }
+
+    /// Check a file, back it up with `backup` appended to its name if given, write the changes
+    /// and update its `XMP` sidecar. Fails only if the file itself could not be written.
+    pub async fn apply(
+        &self,
+        file: &str,
+        changes: &Changes,
+        backup: Option<&str>,
+    ) -> Result<Applied, Error> {
+        let warning = self.check(file).await?;
+        if let Some(suffix) = backup {
+            self.back_up(file, suffix).await?;
+        }
+        let written = self.write(file, changes).await?;
+        let sidecar = self.update_sidecar(file, changes.date_time).await;
+
+        Ok(Applied {
+            warning,
+            exiftool_warnings: written.exiftool_warnings,
+            sidecar,
+            elapsed: written.elapsed,
+        })
+    }
+
+    /// Copy a file and its sidecar to their names with `suffix` appended, unless they already
+    /// have a backup.
+    pub async fn back_up(&self, file: &str, suffix: &str) -> Result<(), Error> {
+        files::back_up(file, suffix).await.map_err(|err| {
+            let err = Error::from(err);
+            Error::new(err.kind(), &format!("could not back up: {}", err))
+        })
+    }
+
+    /// Write the changes to a file, replacing it. QuickTime dates are converted to UTC, as the
+    /// format stores them.
+    pub async fn write(&self, file: &str, changes: &Changes) -> Result<Written, Error> {
+        let mut args = vec![
+            OsString::from("-overwrite_original"),
+            OsString::from("-api"),
+            OsString::from("QuickTimeUTC"),
+        ];
+        args.extend(changes.assignments().into_iter().map(OsString::from));
+        args.push(command_path(file));
+        let output = self.exiftool.run(args).await?;
+
+        Ok(Written {
+            exiftool_warnings: output.stderr,
+            elapsed: output.elapsed,
+        })
+    }
+
+    /// Replace `exif:DateTimeOriginal` in the `XMP` sidecar of a file, if it has one. Returns the
+    /// path of the sidecar. The sidecar is written to a temporary file first and then renamed, so
+    /// it is never left half written.
+    pub async fn update_sidecar(
+        &self,
+        file: &str,
+        date_time_original: DateTime,
+    ) -> Result<Option<OsString>, Error> {
+        let xmp = OsString::from(format!("{}.xmp", file));
+        if !tokio::fs::try_exists(&xmp).await? {
+            return Ok(None);
+        }
+
+        let failed = |err: io::Error| {
+            let err = Error::from(err);
+            Error::new(
+                err.kind(),
+                &format!("could not update {}: {}", xmp.display(), err),
+            )
+        };
+        let contents = tokio::fs::read_to_string(&xmp).await.map_err(failed)?;
+        let updated = replace_attribute(
+            &contents,
+            "exif:DateTimeOriginal",
+            &date_time_original.xmp(),
+        );
+        let mut temporary = xmp.clone();
+        temporary.push(".exif-film-tmp");
+        if let Err(err) = tokio::fs::write(&temporary, updated).await {
+            let _ = tokio::fs::remove_file(&temporary).await;
+            return Err(failed(err));
+        }
+        tokio::fs::rename(&temporary, &xmp).await.map_err(failed)?;
+
+        Ok(Some(xmp))
+    }
+
+    /// Stop the exiftool processes.
+    pub async fn close(&self) {
+        self.exiftool.close().await;
+    }
+}
+
+/// The tags [`ExifDateEditor`] writes to a file.
+#[derive(Clone)]
+pub struct Changes {
+    /// The new date.
+    pub date_time: DateTime,
+    /// Timezone of the date in seconds east of UTC, written to the offset tags.
+    pub offset: Option<i64>,
+    /// The tags the date is written to.
+    pub tags: Vec<DateTag>,
+    /// Also record the date in XMP `CreateDate` truncated to this precision, instead of the full
+    /// date of [`DateTag::XmpCreateDate`].
+    pub precision: Option<Precision>,
+    /// Text for the `UserComment` tag.
+    pub user_comment: Option<String>,
+    /// Also set the file modification time to the date.
+    pub set_mtime: bool,
+}
+
+impl Changes {
+    /// Write `date_time` to the default tag of `file`, see [`DateTag::default_for`].
+    pub fn new(date_time: DateTime, file: &str) -> Self {
+        Changes {
+            date_time,
+            offset: None,
+            tags: vec![DateTag::default_for(file)],
+            precision: None,
+            user_comment: None,
+            set_mtime: false,
+        }
+    }
+
+    /// The exiftool tag assignments, like `-DateTimeOriginal=1999:01:01 00:00:00`.
+    pub fn assignments(&self) -> Vec<String> {
+        let offset = self.offset.map(datetime::format_offset);
+        let mut assignments = Vec::new();
+
+        for tag in &self.tags {
+            if *tag == DateTag::XmpCreateDate && self.precision.is_some() {
+                continue;
+            }
+            assignments.extend(tag.assignments(self.date_time, offset.as_deref()));
+        }
+        if let Some(comment) = &self.user_comment {
+            assignments.push(format!("-UserComment={}", comment));
+        }
+        if let Some(precision) = self.precision {
+            assignments.push(format!(
+                "-XMP-xmp:CreateDate={}",
+                self.date_time.truncate(precision)
+            ));
+        }
+        // Without a timezone exiftool takes the file time to be local time.
+        if self.set_mtime {
+            assignments.push(format!(
+                "-FileModifyDate={}{}",
+                self.date_time,
+                offset.as_deref().unwrap_or_default()
+            ));
+        }
+
+        assignments
+    }
+}
+
+/// A date tag read by [`ExifDateEditor::read_date`].
+pub struct DateRead {
+    /// The value of the tag, if the file has it.
+    pub value: Option<String>,
+    /// Time exiftool took to read the file, without waiting for a free exiftool process.
+    pub elapsed: Duration,
+}
+
+/// What [`ExifDateEditor::write`] did to a file.
+pub struct Written {
+    /// Warnings exiftool printed while writing.
+    pub exiftool_warnings: String,
+    /// Time exiftool took to write the file, without waiting for a free exiftool process.
+    pub elapsed: Duration,
+}
+
+/// What [`ExifDateEditor::apply`] did to a file.
+pub struct Applied {
+    /// Why the file may be damaged, from [`ExifDateEditor::check`].
+    pub warning: Option<&'static str>,
+    /// Warnings exiftool printed while writing.
+    pub exiftool_warnings: String,
+    /// The updated `XMP` sidecar, if the file has one, or why it could not be updated. The file
+    /// itself was written either way.
+    pub sidecar: Result<Option<OsString>, Error>,
+    /// Time exiftool took to write the file, without waiting for a free exiftool process.
+    pub elapsed: Duration,
+}
+
+/// Why reading or writing a file failed.
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    message: String,
+}
+
+/// The cause of an [`Error`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The file is empty, truncated or can't be parsed.
+    Corrupt,
+    /// exiftool can't write this format.
+    Unsupported,
+    /// The file or its directory can't be written.
+    PermissionDenied,
+    /// The disk ran out of space while writing.
+    DiskFull,
+    /// exiftool took longer than the timeout.
+    TimedOut,
+    /// Any other failure of exiftool or of reading the file.
+    Other,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind, message: &str) -> Self {
+        Error {
+            kind,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Sorts IO and exiftool errors by their cause.
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        let kind = match err.kind() {
+            io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem => {
+                ErrorKind::PermissionDenied
+            }
+            io::ErrorKind::StorageFull => ErrorKind::DiskFull,
+            io::ErrorKind::InvalidData => ErrorKind::Corrupt,
+            io::ErrorKind::Unsupported => ErrorKind::Unsupported,
+            io::ErrorKind::TimedOut => ErrorKind::TimedOut,
+            _ => ErrorKind::Other,
+        };
+
+        Error {
+            kind,
+            message: err.to_string(),
+        }
+    }
+}
+
+/// A date tag that an [`ExifDateEditor`] can write.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DateTag {
+    DateTimeOriginal,
+    /// EXIF `DateTimeDigitized`, which exiftool calls `CreateDate`.
+    CreateDate,
+    /// The IFD0 `DateTime` tag, which exiftool calls `ModifyDate`.
+    ModifyDate,
+    XmpCreateDate,
+    IptcDateCreated,
+    /// The `CreateDate`, `MediaCreateDate` and `TrackCreateDate` of QuickTime and MP4 videos.
+    QuickTimeCreateDate,
+}
+
+/// Extensions of the QuickTime based video formats, which have no EXIF dates.
+const QUICKTIME_EXTENSIONS: &[&str] = &["mov", "mp4", "m4v", "3gp", "3g2"];
+
+impl DateTag {
+    /// Parse a tag name like `DateTimeOriginal` or `XMP:CreateDate`, ignoring case.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "datetimeoriginal" => Some(DateTag::DateTimeOriginal),
+            "createdate" | "datetimedigitized" => Some(DateTag::CreateDate),
+            "modifydate" | "datetime" => Some(DateTag::ModifyDate),
+            "xmp:createdate" => Some(DateTag::XmpCreateDate),
+            "iptc:datecreated" => Some(DateTag::IptcDateCreated),
+            "quicktime:createdate" => Some(DateTag::QuickTimeCreateDate),
+            _ => None,
+        }
+    }
+
+    /// The tag that holds the date a file was taken: [`DateTag::QuickTimeCreateDate`] for
+    /// QuickTime and MP4 videos and [`DateTag::DateTimeOriginal`] for everything else.
+    pub fn default_for(file: &str) -> Self {
+        let extension = Path::new(file)
+            .extension()
+            .and_then(OsStr::to_str)
+            .map(str::to_ascii_lowercase);
+
+        match extension {
+            Some(extension) if QUICKTIME_EXTENSIONS.contains(&extension.as_str()) => {
+                DateTag::QuickTimeCreateDate
+            }
+            _ => DateTag::DateTimeOriginal,
+        }
+    }
+
+    /// The exiftool name of the tag.
+    fn name(self) -> &'static str {
+        match self {
+            DateTag::DateTimeOriginal => "DateTimeOriginal",
+            DateTag::CreateDate => "ExifIFD:CreateDate",
+            DateTag::ModifyDate => "IFD0:ModifyDate",
+            DateTag::XmpCreateDate => "XMP-xmp:CreateDate",
+            DateTag::IptcDateCreated => "IPTC:DateCreated",
+            DateTag::QuickTimeCreateDate => "QuickTime:CreateDate",
+        }
+    }
+
+    /// The exiftool assignments writing `date_time` to this tag, with the timezone `offset` where
+    /// the tag has one.
+    fn assignments(self, date_time: DateTime, offset: Option<&str>) -> Vec<String> {
+        let mut assignments = Vec::new();
+        let mut exif = |tag: &str, offset_tag: &str| {
+            assignments.push(format!("-{}={}", tag, date_time));
+            if let Some(offset) = offset {
+                assignments.push(format!("-{}={}", offset_tag, offset));
+            }
+        };
+
+        match self {
+            DateTag::DateTimeOriginal => exif("DateTimeOriginal", "OffsetTimeOriginal"),
+            DateTag::CreateDate => exif("ExifIFD:CreateDate", "OffsetTimeDigitized"),
+            DateTag::ModifyDate => exif("IFD0:ModifyDate", "OffsetTime"),
+            DateTag::XmpCreateDate => assignments.push(format!(
+                "-XMP-xmp:CreateDate={}{}",
+                date_time.xmp(),
+                offset.unwrap_or_default()
+            )),
+            DateTag::IptcDateCreated => {
+                let exif = date_time.to_string();
+                let (date, time) = exif.split_once(' ').unwrap_or((&exif, ""));
+                assignments.push(format!("-IPTC:DateCreated={}", date));
+                assignments.push(format!(
+                    "-IPTC:TimeCreated={}{}",
+                    time,
+                    offset.unwrap_or_default()
+                ));
+            }
+            // Without an offset the date is taken as UTC, like the dates that are resolved
+            // without a timezone, rather than as the timezone of the computer.
+            DateTag::QuickTimeCreateDate => {
+                for tag in ["CreateDate", "MediaCreateDate", "TrackCreateDate"] {
+                    assignments.push(format!(
+                        "-QuickTime:{}={}{}",
+                        tag,
+                        date_time,
+                        offset.unwrap_or("+00:00")
+                    ));
+                }
+            }
+        }
+
+        assignments
+    }
+}
+
+/// Replace the value of every `name="..."` attribute in an XML document.
+fn replace_attribute(xml: &str, name: &str, value: &str) -> String {
+    let prefix = format!("{}=\"", name);
+    let mut replaced = String::with_capacity(xml.len());
+    let mut rest = xml;
+
+    while let Some(start) = rest.find(&prefix) {
+        let after = &rest[start + prefix.len()..];
+        let Some(end) = after.find('"') else {
+            break;
+        };
+        replaced.push_str(&rest[..start + prefix.len()]);
+        replaced.push_str(value);
+        rest = &after[end..];
+    }
+
+    replaced.push_str(rest);
+    replaced
+}
+
+/// A file argument that can't be mistaken for an option by exiftool. Relative paths
+/// starting with `-` get a `./` prefix; other paths, including ones with `@` or newlines, are
+/// passed unchanged as their own argument.
+fn command_path<T>(file: T) -> OsString
+where
+    T: AsRef<OsStr>,
+{
+    let file = file.as_ref();
+    if file.as_encoded_bytes().starts_with(b"-") {
+        let mut path = OsString::from("./");
+        path.push(file);
+        path
+    } else {
+        file.to_os_string()
+    }
+}
+
+/// Formats exiftool cannot write, with advice on what to do instead.
+const UNSUPPORTED_FORMATS: &[(&[&str], &str)] = &[
+    (
+        &["bmp"],
+        "exiftool can't write BMP files, convert the scan to TIFF or PNG",
+    ),
+    (
+        &["avi", "wav"],
+        "exiftool can't write RIFF files, keep the date in an XMP sidecar",
+    ),
+    (
+        &["mkv", "webm"],
+        "exiftool can't write Matroska files, keep the date in an XMP sidecar",
+    ),
+    (
+        &["wmv", "asf"],
+        "exiftool can't write ASF files, keep the date in an XMP sidecar",
+    ),
+];
+
+/// Advice for files whose format exiftool cannot write, based on the extension.
+fn unsupported_format(file: &str) -> Option<&'static str> {
+    let extension = Path::new(file).extension()?.to_str()?.to_ascii_lowercase();
+
+    UNSUPPORTED_FORMATS
+        .iter()
+        .find(|(extensions, _)| extensions.contains(&extension.as_str()))
+        .map(|(_, advice)| *advice)
+}
+
+/// Size of the end of a JPEG searched for the end of image marker, which may be followed by
+/// padding or an appended thumbnail.
+const JPEG_TAIL: u64 = 64 * 1024;
+
+/// Check that a file is non-empty and, for formats with a known signature, that it starts with
+/// that signature. Corrupt files are reported as [`io::ErrorKind::InvalidData`]. JPEGs without an
+/// end of image marker near their end get a warning, as they are either truncated or have a
+/// large trailer like the video of a motion photo.
+async fn check_image<T>(file: T) -> io::Result<Option<&'static str>>
+where
+    T: AsRef<Path>,
+{
+    let path = file.as_ref();
+    let mut image = File::open(path).await?;
+    let size = image.metadata().await?.len();
+    if size == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "file is empty"));
+    }
+
+    let extension = path
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(str::to_ascii_lowercase);
+    let signatures: &[&[u8]] = match extension.as_deref() {
+        Some("jpg" | "jpeg") => &[b"\xFF\xD8\xFF"],
+        Some("tif" | "tiff") => &[b"II*\0", b"MM\0*", b"II+\0", b"MM\0+"],
+        Some("png") => &[b"\x89PNG"],
+        _ => return Ok(None),
+    };
+
+    let mut header = [0; 4];
+    let header = &mut header[..(size as usize).min(4)];
+    image.read_exact(header).await?;
+    if !signatures
+        .iter()
+        .any(|signature| header.starts_with(signature))
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "file does not start with a valid image header",
+        ));
+    }
+
+    if matches!(extension.as_deref(), Some("jpg" | "jpeg")) {
+        let tail = size.min(JPEG_TAIL);
+        let mut end = vec![0; tail as usize];
+        image.seek(SeekFrom::End(-(tail as i64))).await?;
+        image.read_exact(&mut end).await?;
+        if !end.windows(2).any(|marker| marker == b"\xFF\xD9") {
+            return Ok(Some(
+                "no JPEG end of image marker near the end of the file, it may be truncated",
+            ));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_path_prefixes_only_leading_dashes() {
+        let cases = [
+            ("-x.jpg", "./-x.jpg"),
+            ("--", "./--"),
+            ("-", "./-"),
+            ("@a.jpg", "@a.jpg"),
+            ("a\nb.jpg", "a\nb.jpg"),
+            ("/tmp/-x.jpg", "/tmp/-x.jpg"),
+            ("x-.jpg", "x-.jpg"),
+            ("./-x.jpg", "./-x.jpg"),
+        ];
+        for (file, expected) in cases {
+            assert_eq!(command_path(file), OsString::from(expected), "{:?}", file);
+        }
+    }
+
+    #[test]
+    fn replace_attributes() {
+        let name = "exif:DateTimeOriginal";
+        let value = "1999-01-01T00:00:00";
+        let cases = [
+            (
+                r#"<d exif:DateTimeOriginal="2001-02-03T04:05:06"/>"#,
+                r#"<d exif:DateTimeOriginal="1999-01-01T00:00:00"/>"#,
+            ),
+            (
+                r#"<d exif:DateTimeOriginal=""/>"#,
+                r#"<d exif:DateTimeOriginal="1999-01-01T00:00:00"/>"#,
+            ),
+            (
+                r#"<a exif:DateTimeOriginal="x"/><b exif:DateTimeOriginal="y"/>"#,
+                r#"<a exif:DateTimeOriginal="1999-01-01T00:00:00"/><b exif:DateTimeOriginal="1999-01-01T00:00:00"/>"#,
+            ),
+            (
+                r#"<d xmp:CreateDate="2001-02-03"/>"#,
+                r#"<d xmp:CreateDate="2001-02-03"/>"#,
+            ),
+            (
+                r#"<d exif:DateTimeOriginal="unterminated"#,
+                r#"<d exif:DateTimeOriginal="unterminated"#,
+            ),
+            ("", ""),
+        ];
+        for (xml, expected) in cases {
+            assert_eq!(replace_attribute(xml, name, value), expected, "{}", xml);
+        }
+    }
+
+    #[test]
+    fn change_assignments() {
+        let date_time = DateTime::date(1999, 1, 1).with_time(Some(12), Some(34), Some(56));
+        let changes = |tags: &[DateTag], offset, precision| Changes {
+            date_time,
+            offset,
+            tags: tags.to_vec(),
+            precision,
+            user_comment: None,
+            set_mtime: false,
+        };
+        let cases: [(Changes, &[&str]); 12] = [
+            (
+                changes(&[DateTag::DateTimeOriginal], None, None),
+                &["-DateTimeOriginal=1999:01:01 12:34:56"],
+            ),
+            (
+                changes(&[DateTag::DateTimeOriginal], Some(7200), None),
+                &[
+                    "-DateTimeOriginal=1999:01:01 12:34:56",
+                    "-OffsetTimeOriginal=+02:00",
+                ],
+            ),
+            (
+                changes(
+                    &[DateTag::CreateDate, DateTag::ModifyDate],
+                    Some(-19_800),
+                    None,
+                ),
+                &[
+                    "-ExifIFD:CreateDate=1999:01:01 12:34:56",
+                    "-OffsetTimeDigitized=-05:30",
+                    "-IFD0:ModifyDate=1999:01:01 12:34:56",
+                    "-OffsetTime=-05:30",
+                ],
+            ),
+            (
+                changes(&[DateTag::QuickTimeCreateDate], None, None),
+                &[
+                    "-QuickTime:CreateDate=1999:01:01 12:34:56+00:00",
+                    "-QuickTime:MediaCreateDate=1999:01:01 12:34:56+00:00",
+                    "-QuickTime:TrackCreateDate=1999:01:01 12:34:56+00:00",
+                ],
+            ),
+            (
+                changes(&[DateTag::QuickTimeCreateDate], Some(32_400), None),
+                &[
+                    "-QuickTime:CreateDate=1999:01:01 12:34:56+09:00",
+                    "-QuickTime:MediaCreateDate=1999:01:01 12:34:56+09:00",
+                    "-QuickTime:TrackCreateDate=1999:01:01 12:34:56+09:00",
+                ],
+            ),
+            (
+                changes(&[DateTag::XmpCreateDate], None, None),
+                &["-XMP-xmp:CreateDate=1999-01-01T12:34:56"],
+            ),
+            (
+                changes(&[DateTag::XmpCreateDate], Some(0), None),
+                &["-XMP-xmp:CreateDate=1999-01-01T12:34:56+00:00"],
+            ),
+            (
+                changes(&[DateTag::IptcDateCreated], Some(3600), None),
+                &[
+                    "-IPTC:DateCreated=1999:01:01",
+                    "-IPTC:TimeCreated=12:34:56+01:00",
+                ],
+            ),
+            (
+                changes(&[DateTag::DateTimeOriginal], None, Some(Precision::Year)),
+                &[
+                    "-DateTimeOriginal=1999:01:01 12:34:56",
+                    "-XMP-xmp:CreateDate=1999",
+                ],
+            ),
+            (
+                changes(
+                    &[DateTag::DateTimeOriginal, DateTag::XmpCreateDate],
+                    Some(7200),
+                    Some(Precision::Month),
+                ),
+                &[
+                    "-DateTimeOriginal=1999:01:01 12:34:56",
+                    "-OffsetTimeOriginal=+02:00",
+                    "-XMP-xmp:CreateDate=1999-01",
+                ],
+            ),
+            (
+                Changes {
+                    user_comment: Some("Ilford HP5+ @1600;Rodinal;Nikon FM2;50mm".to_string()),
+                    set_mtime: true,
+                    ..changes(&[DateTag::DateTimeOriginal], None, Some(Precision::Day))
+                },
+                &[
+                    "-DateTimeOriginal=1999:01:01 12:34:56",
+                    "-UserComment=Ilford HP5+ @1600;Rodinal;Nikon FM2;50mm",
+                    "-XMP-xmp:CreateDate=1999-01-01",
+                    "-FileModifyDate=1999:01:01 12:34:56",
+                ],
+            ),
+            (
+                Changes {
+                    set_mtime: true,
+                    ..changes(&[], Some(-3600), None)
+                },
+                &["-FileModifyDate=1999:01:01 12:34:56-01:00"],
+            ),
+        ];
+        for (changes, expected) in cases {
+            assert_eq!(changes.assignments(), expected);
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use std::ffi::OsString;
+use std::io;
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command};
+use tokio::sync::Semaphore;
+
+/// Text printed by one exiftool command.
+pub struct Output {
+    pub stdout: String,
+    pub stderr: String,
+    /// Time exiftool took for the command, without waiting for a free process.
+    pub elapsed: Duration,
+}
+
+/// A pool of exiftool processes kept running with `-stay_open`, so that a batch doesn't pay for
+/// starting a new exiftool (and perl) for every file.
+pub struct Exiftool {
+    idle: Mutex<Vec<Process>>,
+    permits: Semaphore,
+    timeout: Option<Duration>,
+}
+
+impl Exiftool {
+    /// A pool of at most `size` processes, started as they are needed. Commands that can't be
+    /// sent to them count against the same limit. Commands that take longer than `timeout` fail
+    /// and their process is killed.
+    pub fn new(size: usize, timeout: Option<Duration>) -> Self {
+        Exiftool {
+            idle: Mutex::new(Vec::new()),
+            permits: Semaphore::new(size),
+            timeout,
+        }
+    }
+
+    /// Run exiftool with the given arguments. Fails with the first error exiftool reports.
+    pub async fn run(&self, args: Vec<OsString>) -> io::Result<Output> {
+        let _permit = self.permits.acquire().await.map_err(io::Error::other)?;
+
+        // Argument files hold one trimmed argument per line, so anything else gets its own run.
+        if !args.iter().all(fits_argfile) {
+            return self.limit(run_once(args)).await;
+        }
+
+        let process = self.idle.lock().unwrap().pop();
+        let mut process = match process {
+            Some(process) => process,
+            None => Process::spawn()?,
+        };
+
+        // A process that failed to answer in time is dropped, which kills it.
+        let output = self.limit(process.execute(&args)).await?;
+        self.idle.lock().unwrap().push(process);
+
+        check_errors(output)
+    }
+
+    /// Fail a command that takes longer than the timeout.
+    async fn limit<F>(&self, command: F) -> io::Result<Output>
+    where
+        F: Future<Output = io::Result<Output>>,
+    {
+        let Some(timeout) = self.timeout else {
+            return command.await;
+        };
+
+        tokio::time::timeout(timeout, command)
+            .await
+            .unwrap_or_else(|_| {
+                Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("exiftool timed out after {}s", timeout.as_secs()),
+                ))
+            })
+    }
+
+    /// Start a process ahead of the first command, to fail early if exiftool can't be run.
+    pub async fn start(&self) -> io::Result<()> {
+        let _permit = self.permits.acquire().await.map_err(io::Error::other)?;
+        let process = Process::spawn()?;
+        self.idle.lock().unwrap().push(process);
+
+        Ok(())
+    }
+
+    /// Ask the idle processes to exit and wait for them.
+    pub async fn close(&self) {
+        let processes = std::mem::take(&mut *self.idle.lock().unwrap());
+
+        for mut process in processes {
+            if process
+                .stdin
+                .write_all(b"-stay_open\nFalse\n")
+                .await
+                .is_ok()
+            {
+                let _ = process.stdin.flush().await;
+                let _ = process.child.wait().await;
+            }
+        }
+    }
+}
+
+/// A running `exiftool -stay_open True -@ -`.
+struct Process {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    stderr: BufReader<ChildStderr>,
+    /// Number of the next command, used to find the end of its output.
+    next: u64,
+}
+
+impl Process {
+    fn spawn() -> io::Result<Self> {
+        let mut child = Command::new("exiftool")
+            .args(["-stay_open", "True", "-@", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(spawn_error)?;
+
+        Ok(Process {
+            stdin: child.stdin.take().expect("stdin is piped"),
+            stdout: BufReader::new(child.stdout.take().expect("stdout is piped")),
+            stderr: BufReader::new(child.stderr.take().expect("stderr is piped")),
+            child,
+            next: 0,
+        })
+    }
+
+    /// Run one command. exiftool marks the end of its output with `{ready<n>}` on stdout for
+    /// `-execute<n>`, and `-echo4` prints the same marker to stderr once the command is done.
+    async fn execute(&mut self, args: &[OsString]) -> io::Result<Output> {
+        let started = Instant::now();
+        self.next += 1;
+        let marker = format!("{{ready{}}}", self.next);
+
+        let mut command = Vec::new();
+        for arg in args {
+            command.extend_from_slice(arg.as_encoded_bytes());
+            command.push(b'\n');
+        }
+        command
+            .extend_from_slice(format!("-echo4\n{}\n-execute{}\n", marker, self.next).as_bytes());
+
+        self.stdin.write_all(&command).await?;
+        self.stdin.flush().await?;
+
+        let (stdout, stderr) = tokio::try_join!(
+            read_until(&mut self.stdout, &marker),
+            read_until(&mut self.stderr, &marker)
+        )?;
+
+        Ok(Output {
+            stdout,
+            stderr,
+            elapsed: started.elapsed(),
+        })
+    }
+}
+
+/// Read lines up to a line holding only `marker`.
+async fn read_until<R>(reader: &mut BufReader<R>, marker: &str) -> io::Result<String>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut output = String::new();
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "exiftool exited unexpectedly",
+            ));
+        }
+        if line.trim_end() == marker {
+            return Ok(output);
+        }
+        output.push_str(&line);
+    }
+}
+
+/// Whether an argument survives being passed as a line of an argument file.
+fn fits_argfile(arg: &OsString) -> bool {
+    let bytes = arg.as_encoded_bytes();
+
+    !bytes.is_empty()
+        && !bytes.contains(&b'\n')
+        && !bytes.contains(&b'\r')
+        && !bytes.starts_with(b"#")
+        && !bytes.first().is_some_and(u8::is_ascii_whitespace)
+        && !bytes.last().is_some_and(u8::is_ascii_whitespace)
+}
+
+/// The error of a failed start of exiftool, which names exiftool rather than the file it was
+/// started for when it isn't installed.
+fn spawn_error(err: io::Error) -> io::Error {
+    match err.kind() {
+        io::ErrorKind::NotFound => io::Error::new(
+            io::ErrorKind::NotFound,
+            "exiftool not found on PATH, install it from https://exiftool.org",
+        ),
+        _ => err,
+    }
+}
+
+/// Run a separate exiftool process for a single command.
+async fn run_once(args: Vec<OsString>) -> io::Result<Output> {
+    let started = Instant::now();
+    let output = Command::new("exiftool")
+        .args(args)
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(spawn_error)?;
+    let output_text = Output {
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        elapsed: started.elapsed(),
+    };
+
+    if output.status.success() {
+        check_errors(output_text)
+    } else {
+        check_errors(output_text).and(Err(io::Error::other("exiftool returned non‑zero status")))
+    }
+}
+
+/// Turn the first `Error:` line exiftool printed into an error, with a kind matching its cause.
+fn check_errors(output: Output) -> io::Result<Output> {
+    match output
+        .stderr
+        .lines()
+        .find_map(|line| line.strip_prefix("Error: "))
+    {
+        Some(error) => Err(io::Error::new(error_kind(error), error.to_string())),
+        None => Ok(output),
+    }
+}
+
+/// Messages of exiftool errors by the kind of their cause.
+const ERROR_KINDS: &[(&[&str], io::ErrorKind)] = &[
+    (
+        &[
+            "permission denied",
+            "read-only",
+            "error creating",
+            "error renaming",
+        ],
+        io::ErrorKind::PermissionDenied,
+    ),
+    (
+        &["no space left", "disk full", "error writing"],
+        io::ErrorKind::StorageFull,
+    ),
+    (
+        &["not a valid", "format error", "corrupt", "truncated"],
+        io::ErrorKind::InvalidData,
+    ),
+    (
+        &[
+            "not yet supported",
+            "unknown file type",
+            "can't currently write",
+        ],
+        io::ErrorKind::Unsupported,
+    ),
+];
+
+/// The kind of an exiftool error message.
+fn error_kind(message: &str) -> io::ErrorKind {
+    let message = message.to_ascii_lowercase();
+
+    ERROR_KINDS
+        .iter()
+        .find(|(messages, _)| messages.iter().any(|m| message.contains(m)))
+        .map_or(io::ErrorKind::Other, |(_, kind)| *kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fits_argfile_refuses_lines_the_argument_file_would_change() {
+        let cases = [
+            ("scan01.tif", true),
+            ("-DateTimeOriginal=1999:01:01 00:00:00", true),
+            ("@a.jpg", true),
+            ("a#b.jpg", true),
+            ("", false),
+            ("a\nb.jpg", false),
+            ("a\rb.jpg", false),
+            ("#a.jpg", false),
+            (" a.jpg", false),
+            ("a.jpg ", false),
+            ("a.jpg\t", false),
+        ];
+        for (arg, expected) in cases {
+            assert_eq!(fits_argfile(&OsString::from(arg)), expected, "{:?}", arg);
+        }
+    }
+}
@@ -0,0 +1,702 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Read a list of files, one per line, or from stdin if `list` is `-`.
+///
+/// Also reads `.m3u` playlists, whose `#` lines are comments and whose relative paths are
+/// relative to the playlist, and the `file://` URLs that file managers put in exported or dragged
+/// lists. If `nul` separated, as printed by `find -print0`, the entries are taken as they are.
+pub fn read_file_list(list: &str, nul: bool) -> io::Result<Vec<String>> {
+    let content = if list == "-" {
+        io::read_to_string(io::stdin())?
+    } else {
+        fs::read_to_string(list)?
+    };
+
+    if nul {
+        return Ok(content
+            .split('\0')
+            .filter(|entry| !entry.is_empty())
+            .map(str::to_string)
+            .collect());
+    }
+
+    let playlist = Path::new(list).extension().is_some_and(|extension| {
+        extension.eq_ignore_ascii_case("m3u") || extension.eq_ignore_ascii_case("m3u8")
+    });
+    let directory = Path::new(list).parent().filter(|_| playlist);
+
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let path = decode_entry(line).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid file URL `{}` in {}", line, list),
+                )
+            })?;
+            Ok(match directory {
+                Some(directory) if Path::new(&path).is_relative() => {
+                    directory.join(path).to_string_lossy().into_owned()
+                }
+                _ => path,
+            })
+        })
+        .collect()
+}
+
+/// Turn a list entry into a path, decoding `file://` URLs.
+fn decode_entry(entry: &str) -> Option<String> {
+    let Some(url) = entry.strip_prefix("file://") else {
+        return Some(entry.to_string());
+    };
+
+    // The authority is empty or `localhost` for local files.
+    let path = url.strip_prefix("localhost").unwrap_or(url);
+    if !path.starts_with('/') {
+        return None;
+    }
+    let path = percent_decode(path)?;
+
+    // Windows drive paths are written as `file:///C:/...`.
+    let bytes = path.as_bytes();
+    if bytes.len() >= 3 && bytes[0] == b'/' && bytes[1].is_ascii_alphabetic() && bytes[2] == b':' {
+        return Some(path[1..].to_string());
+    }
+
+    Some(path)
+}
+
+/// Decode `%XX` escapes of a URL path.
+fn percent_decode(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = tail
+                .get(..2)
+                .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))?;
+            bytes.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+
+    String::from_utf8(bytes).ok()
+}
+
+/// Replace the arguments containing `*`, `?` or `[...]` wildcards by the sorted paths they
+/// match, for shells that don't expand them. Arguments that match nothing or name an existing
+/// file are kept as they are.
+pub fn expand_globs(args: Vec<String>) -> Vec<String> {
+    let mut paths = Vec::new();
+
+    for arg in args {
+        let matches = if is_glob(&arg) && !Path::new(&arg).exists() {
+            glob(&arg)
+        } else {
+            Vec::new()
+        };
+        let expanded = if matches.is_empty() {
+            vec![arg]
+        } else {
+            matches
+        };
+
+        paths.extend(expanded);
+    }
+
+    paths
+}
+
+/// Keep only the first of the paths that name the same file, like `scans/a.jpg` and
+/// `./scans//a.jpg` or a symlink to it. Paths that don't exist are compared as written, without
+/// `.` components and repeated separators.
+pub async fn remove_duplicates(paths: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut unique = Vec::with_capacity(paths.len());
+
+    for path in paths {
+        let key = match tokio::fs::canonicalize(&path).await {
+            Ok(canonical) => canonical,
+            Err(_) => Path::new(&path).components().collect(),
+        };
+        if seen.insert(key) {
+            unique.push(path);
+        }
+    }
+
+    unique
+}
+
+fn is_glob(arg: &str) -> bool {
+    arg.contains(['*', '?', '['])
+}
+
+/// The sorted paths matching a pattern, one path component at a time.
+fn glob(pattern: &str) -> Vec<String> {
+    let mut paths = vec![PathBuf::new()];
+
+    for component in Path::new(pattern).components() {
+        let Component::Normal(name) = component else {
+            paths.iter_mut().for_each(|path| path.push(component));
+            continue;
+        };
+        let Some(name) = name.to_str().filter(|name| is_glob(name)) else {
+            paths.iter_mut().for_each(|path| path.push(name));
+            continue;
+        };
+
+        paths = paths
+            .iter()
+            .flat_map(|directory| {
+                let entries = fs::read_dir(if directory.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    directory
+                });
+                entries
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .filter(|entry| {
+                        entry
+                            .file_name()
+                            .to_str()
+                            .is_some_and(|entry| matches_glob(name, entry))
+                    })
+                    .map(|entry| directory.join(entry.file_name()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+    }
+
+    let mut paths: Vec<String> = paths
+        .into_iter()
+        .filter(|path| path.exists())
+        .filter_map(|path| path.into_os_string().into_string().ok())
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Whether a file name matches a pattern of `*`, `?` and `[...]` wildcards. Like in shells,
+/// hidden files only match patterns that start with a `.`.
+fn matches_glob(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position in the pattern after the last `*` and in the name where it started matching.
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        let step = match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+                continue;
+            }
+            Some('?') => Some(1),
+            Some('[') => match_class(&pattern[p..], name[n]),
+            Some(c) => (*c == name[n]).then_some(1),
+            None => None,
+        };
+
+        match (step, star) {
+            (Some(length), _) => {
+                p += length;
+                n += 1;
+            }
+            (None, Some((after, start))) => {
+                p = after;
+                n = start + 1;
+                star = Some((after, start + 1));
+            }
+            (None, None) => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Match a character against a `[...]` class at the start of `pattern`, returning the length of
+/// the class if it matches. An unclosed `[` only matches itself.
+fn match_class(pattern: &[char], c: char) -> Option<usize> {
+    let negated = matches!(pattern.get(1), Some('!' | '^'));
+    let start = if negated { 2 } else { 1 };
+    // A `]` right after the opening bracket is part of the class.
+    let Some(end) = pattern
+        .iter()
+        .skip(start + 1)
+        .position(|c| *c == ']')
+        .map(|end| end + start + 1)
+    else {
+        return (c == '[').then_some(1);
+    };
+
+    let class = &pattern[start..end];
+    let mut matched = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            matched |= (class[i]..=class[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            matched |= class[i] == c;
+            i += 1;
+        }
+    }
+
+    (matched != negated).then_some(end + 1)
+}
+
+/// Extensions of the image and video formats that exiftool can write, which are picked up from
+/// directories unless other extensions are given.
+pub const IMAGE_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "jpe", "tif", "tiff", "png", "webp", "heic", "heif", "avif", "jxl", "jp2",
+    "gif", "psd", "dng", "3fr", "arw", "cr2", "cr3", "crw", "erf", "iiq", "mef", "mos", "mrw",
+    "nef", "nrw", "orf", "pef", "raf", "rw2", "rwl", "sr2", "srw", "x3f", "mov", "mp4", "m4v",
+    "3gp", "3g2",
+];
+
+/// Names of system directories with trash and thumbnails that are never descended into.
+const SKIPPED_DIRECTORIES: &[&str] = &[
+    "$RECYCLE.BIN",
+    "System Volume Information",
+    "lost+found",
+    "@eaDir",
+    "#recycle",
+];
+
+/// Extensions of directories with previews that are never descended into.
+const SKIPPED_DIRECTORY_EXTENSIONS: &[&str] = &["lrdata"];
+
+/// Directories right below the root of a drive that hold the system and installed programs, which
+/// are refused with all directories in them. The root itself is refused too.
+const SYSTEM_DIRECTORIES: &[&str] = &[
+    "bin",
+    "boot",
+    "dev",
+    "etc",
+    "lib",
+    "lib32",
+    "lib64",
+    "opt",
+    "proc",
+    "run",
+    "sbin",
+    "snap",
+    "sys",
+    "usr",
+    "var",
+    "Applications",
+    "Library",
+    "System",
+    "Program Files",
+    "Program Files (x86)",
+    "ProgramData",
+    "Windows",
+];
+
+/// Extensions of app bundles and photo libraries, whose files are managed by the app and which are
+/// refused.
+const APP_DIRECTORY_EXTENSIONS: &[&str] = &["app", "photoslibrary", "aplibrary", "cocatalog"];
+
+/// Replace directories in `paths` by the image files they contain, descending into
+/// subdirectories if `recursive`. Found files must have one of `extensions`, or of
+/// [`IMAGE_EXTENSIONS`] if none are given. XMP sidecars, hidden files and trash, thumbnail and
+/// preview directories are never included, other paths are kept as they are.
+///
+/// Directories of the system and of apps are refused with an error, whether they are in `paths`
+/// or found while descending, unless `allow_system`. Directories in `paths` that can't be read
+/// are an error too, while subdirectories that can't be read are skipped and returned in
+/// [`Expanded::unreadable`].
+pub async fn expand_directories(
+    paths: Vec<String>,
+    recursive: bool,
+    extensions: &[String],
+    allow_system: bool,
+) -> io::Result<Expanded> {
+    let mut expanded = Expanded {
+        files: Vec::new(),
+        unreadable: Vec::new(),
+    };
+    // Each directory with its canonical path, to recognise system directories however they are
+    // named, and whether it was found while descending.
+    let mut directories = Vec::new();
+
+    for path in paths {
+        if tokio::fs::metadata(&path).await.is_ok_and(|m| m.is_dir()) {
+            let canonical = tokio::fs::canonicalize(&path).await?;
+            directories.push((PathBuf::from(path), canonical, false));
+        } else {
+            expanded.files.push(path);
+        }
+    }
+
+    while let Some((directory, canonical, found)) = directories.pop() {
+        if !allow_system && is_protected_directory(&canonical) {
+            return Err(io::Error::other(format!(
+                "refusing to tag the files in {}, which belongs to the system or an app. Pass \
+                --i-know-what-im-doing to tag them anyway",
+                directory.display()
+            )));
+        }
+
+        let entries = match read_directory(&directory).await {
+            Ok(entries) => entries,
+            Err(err) if found => {
+                expanded.unreadable.push((directory, err));
+                continue;
+            }
+            Err(err) => {
+                return Err(io::Error::new(
+                    err.kind(),
+                    format!("could not read directory {}: {}", directory.display(), err),
+                ));
+            }
+        };
+
+        for (path, is_dir) in entries {
+            let name = path.file_name().unwrap_or_default();
+            if name.as_encoded_bytes().starts_with(b".") {
+                continue;
+            } else if is_dir {
+                if recursive && !is_skipped_directory(&path) {
+                    let canonical = canonical.join(name);
+                    directories.push((path, canonical, true));
+                }
+            } else if is_image(&path, extensions) {
+                let path = path.into_os_string().into_string().map_err(|path| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{} is not valid UTF-8", path.display()),
+                    )
+                })?;
+                expanded.files.push(path);
+            }
+        }
+    }
+
+    Ok(expanded)
+}
+
+/// The files found by [`expand_directories`].
+pub struct Expanded {
+    pub files: Vec<String>,
+    /// Subdirectories that were skipped as they could not be read, with the reason.
+    pub unreadable: Vec<(PathBuf, io::Error)>,
+}
+
+/// The entries of a directory, and whether each is a directory itself.
+async fn read_directory(directory: &Path) -> io::Result<Vec<(PathBuf, bool)>> {
+    let mut entries = tokio::fs::read_dir(directory).await?;
+    let mut paths = Vec::new();
+
+    while let Some(entry) = entries.next_entry().await? {
+        paths.push((entry.path(), entry.file_type().await?.is_dir()));
+    }
+
+    Ok(paths)
+}
+
+/// Whether a subdirectory holds trash, thumbnails or previews rather than scans.
+fn is_skipped_directory(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+    SKIPPED_DIRECTORIES
+        .iter()
+        .any(|skipped| skipped.eq_ignore_ascii_case(&name))
+        || has_extension(path, SKIPPED_DIRECTORY_EXTENSIONS)
+}
+
+/// Whether a canonical directory path is the root of a drive, in a [`SYSTEM_DIRECTORIES`]
+/// directory or in an app bundle or photo library.
+fn is_protected_directory(canonical: &Path) -> bool {
+    let mut names = canonical
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        });
+
+    let system = match names.next() {
+        None => true,
+        Some(top) => SYSTEM_DIRECTORIES
+            .iter()
+            .any(|system| system.eq_ignore_ascii_case(&top)),
+    };
+    system
+        || canonical
+            .ancestors()
+            .any(|ancestor| has_extension(ancestor, APP_DIRECTORY_EXTENSIONS))
+}
+
+/// Whether a path has one of the lowercase `extensions`, ignoring case.
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension().is_some_and(|extension| {
+        extensions.contains(&extension.to_string_lossy().to_ascii_lowercase().as_str())
+    })
+}
+
+/// Whether a file found in a directory should be processed.
+fn is_image(path: &Path, extensions: &[String]) -> bool {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+
+    match extension.as_deref() {
+        Some("xmp") | None => false,
+        Some(extension) if extensions.is_empty() => IMAGE_EXTENSIONS.contains(&extension),
+        Some(extension) => extensions.iter().any(|e| e == extension),
+    }
+}
+
+/// Copy a file and its XMP sidecar, if any, to the same name with `suffix` appended. Existing
+/// backups are kept, so they always hold the file from before the first run.
+pub async fn back_up(file: &str, suffix: &str) -> io::Result<()> {
+    for path in [file.to_string(), format!("{}.xmp", file)] {
+        let backup = format!("{}{}", path, suffix);
+        if tokio::fs::try_exists(&path).await? && !tokio::fs::try_exists(&backup).await? {
+            tokio::fs::copy(&path, &backup).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Move the backups made by [`back_up`] back in place of a file and its sidecar. Returns
+/// whether the file had a backup.
+pub async fn restore(file: &str, suffix: &str) -> io::Result<bool> {
+    let backup = format!("{}{}", file, suffix);
+    if !tokio::fs::try_exists(&backup).await? {
+        return Ok(false);
+    }
+    tokio::fs::rename(&backup, file).await?;
+
+    let sidecar = format!("{}.xmp", file);
+    let sidecar_backup = format!("{}{}", sidecar, suffix);
+    if tokio::fs::try_exists(&sidecar_backup).await? {
+        tokio::fs::rename(&sidecar_backup, &sidecar).await?;
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_patterns() {
+        let cases = [
+            ("*.jpg", "scan01.jpg", true),
+            ("*.jpg", "scan01.JPG", false),
+            ("*.jpg", ".hidden.jpg", false),
+            (".*.jpg", ".hidden.jpg", true),
+            ("*", "", true),
+            ("*", "a", true),
+            ("?", "", false),
+            ("?", "é", true),
+            ("scan??.tif", "scan01.tif", true),
+            ("scan??.tif", "scan1.tif", false),
+            ("*a*b*", "xxaxxbxx", true),
+            ("*a*b", "xxaxxbxxc", false),
+            ("a*", "b", false),
+            ("[abc].jpg", "b.jpg", true),
+            ("[abc].jpg", "d.jpg", false),
+            ("[a-c].jpg", "b.jpg", true),
+            ("[!a-c].jpg", "d.jpg", true),
+            ("[^a-c].jpg", "b.jpg", false),
+            ("[]].jpg", "].jpg", true),
+            ("[!]].jpg", "].jpg", false),
+            ("[a-].jpg", "-.jpg", true),
+            ("[.jpg", "[.jpg", true),
+            ("[.jpg", "a.jpg", false),
+            ("IMG_[0-9][0-9]*.tif", "IMG_0001.tif", true),
+        ];
+        for (pattern, name, expected) in cases {
+            assert_eq!(
+                matches_glob(pattern, name),
+                expected,
+                "{} {}",
+                pattern,
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn glob_classes() {
+        let cases = [
+            ("[abc]", 'a', Some(5)),
+            ("[abc]x", 'd', None),
+            ("[!abc]", 'd', Some(6)),
+            ("[^abc]", 'a', None),
+            ("[]a]", ']', Some(4)),
+            ("[a-z0-9]", '5', Some(8)),
+            ("[z-a]", 'm', None),
+            ("[abc", '[', Some(1)),
+            ("[abc", 'a', None),
+            ("[]", ']', None),
+        ];
+        for (pattern, c, expected) in cases {
+            let pattern: Vec<char> = pattern.chars().collect();
+            assert_eq!(match_class(&pattern, c), expected, "{:?} {}", pattern, c);
+        }
+    }
+
+    #[test]
+    fn percent_decoding() {
+        let cases = [
+            ("/scans/roll%201/a.jpg", Some("/scans/roll 1/a.jpg")),
+            ("%2F%2f", Some("//")),
+            ("caf%C3%A9", Some("café")),
+            ("plain", Some("plain")),
+            ("", Some("")),
+            ("100%", None),
+            ("%2", None),
+            ("%zz", None),
+            ("%FF", None),
+            ("%+1", None),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(percent_decode(value).as_deref(), expected, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn skipped_directories() {
+        let cases = [
+            ("/scans/roll 1", false),
+            ("/scans/2024.05", false),
+            ("/Volumes/card/$RECYCLE.BIN", true),
+            ("/Volumes/card/System Volume Information", true),
+            ("/volume1/photo/@eaDir", true),
+            ("/volume1/photo/@EADIR", true),
+            ("/scans/Catalog Previews.lrdata", true),
+            ("/scans/Viewer.app", false),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(is_skipped_directory(Path::new(path)), expected, "{}", path);
+        }
+    }
+
+    #[test]
+    fn protected_directories() {
+        let cases = [
+            ("/", true),
+            ("/usr", true),
+            ("/usr/share/icons", true),
+            ("/etc", true),
+            ("/System/Library", true),
+            ("/Library/Desktop Pictures", true),
+            ("/Applications/Viewer.app", true),
+            (
+                "/Users/a/Pictures/Photos Library.photoslibrary/originals",
+                true,
+            ),
+            ("/Users/a/Downloads/Viewer.APP/Contents/Resources", true),
+            ("/home/a/scans", false),
+            ("/home/a/usr", false),
+            ("/Users/a/Library", false),
+            ("/Volumes/card/DCIM", false),
+            ("/tmp/roll.2024", false),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(
+                is_protected_directory(Path::new(path)),
+                expected,
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn decode_entries() {
+        let cases = [
+            ("scans/a.jpg", Some("scans/a.jpg")),
+            ("/scans/a b.jpg", Some("/scans/a b.jpg")),
+            ("file:///scans/roll%201/a.jpg", Some("/scans/roll 1/a.jpg")),
+            ("file://localhost/scans/a.jpg", Some("/scans/a.jpg")),
+            ("file:///C:/Scans/a%C3%A9.jpg", Some("C:/Scans/aé.jpg")),
+            ("file://server/scans/a.jpg", None),
+            ("file:///scans/a%2", None),
+        ];
+        for (entry, expected) in cases {
+            assert_eq!(decode_entry(entry).as_deref(), expected, "{}", entry);
+        }
+    }
+
+    #[test]
+    fn file_lists() {
+        let directory =
+            std::env::temp_dir().join(format!("exif-film-lists-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let list = |name: &str, content: &str| {
+            let path = directory.join(name);
+            fs::write(&path, content).unwrap();
+            path.to_string_lossy().into_owned()
+        };
+        let relative = |name: &str| directory.join(name).to_string_lossy().into_owned();
+
+        let cases = [
+            (
+                list(
+                    "list.txt",
+                    "a.jpg\n# comment\n\n  b c.jpg  \r\nfile:///x/d%20e.jpg\n",
+                ),
+                false,
+                vec![
+                    "a.jpg".to_string(),
+                    "b c.jpg".to_string(),
+                    "/x/d e.jpg".to_string(),
+                ],
+            ),
+            (
+                list(
+                    "list.m3u",
+                    "#EXTM3U\n#EXTINF:-1,a\na.jpg\nroll/b.jpg\n/x/c.jpg\n",
+                ),
+                false,
+                vec![
+                    relative("a.jpg"),
+                    relative("roll/b.jpg"),
+                    "/x/c.jpg".to_string(),
+                ],
+            ),
+            (list("list.M3U8", "a.jpg\n"), false, vec![relative("a.jpg")]),
+            (
+                list("list.nul", "a.jpg\0# b.jpg\0 c.jpg\n\0\0"),
+                true,
+                vec![
+                    "a.jpg".to_string(),
+                    "# b.jpg".to_string(),
+                    " c.jpg\n".to_string(),
+                ],
+            ),
+        ];
+        for (path, nul, expected) in cases {
+            assert_eq!(read_file_list(&path, nul).unwrap(), expected, "{}", path);
+        }
+
+        let invalid = list("invalid.txt", "a.jpg\nfile://server/b.jpg\n");
+        let err = read_file_list(&invalid, false).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(read_file_list(&relative("missing.txt"), false).is_err());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,262 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use crate::datetime::{self, DateTime};
+
+/// Files whose name without extension sorts between `first` and `last` (inclusive), which are
+/// dated from `start` instead of the date argument.
+pub struct FileRange {
+    pub first: String,
+    pub last: String,
+    pub start: DateTime,
+}
+
+impl FileRange {
+    /// Parse a `<first>..<last>=<date>` range specification, with the date as read by
+    /// [`datetime::parse_start`].
+    pub fn parse(value: &str, tz: i64, clamp_day: bool) -> Option<Self> {
+        let (names, date) = value.rsplit_once('=')?;
+        let (first, last) = names.split_once("..")?;
+        let start = datetime::parse_start(date, tz, clamp_day)?;
+
+        Some(FileRange {
+            first: first.to_string(),
+            last: last.to_string(),
+            start,
+        })
+    }
+
+    /// Whether the name of a file without extension is in the range.
+    pub fn contains(&self, file: &str) -> bool {
+        let stem = Path::new(file)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy())
+            .unwrap_or_default();
+
+        self.first.as_str() <= &*stem && &*stem <= self.last.as_str()
+    }
+}
+
+/// Sort key grouping files of the same frame, e.g. `scan01.tif` and `scan01.jpg`, together.
+pub fn frame_key(file: &str) -> (PathBuf, Option<&OsStr>) {
+    let path = Path::new(file);
+    (path.with_extension(""), path.extension())
+}
+
+/// Number sorted files by frame. Files that only differ by extension are the same frame and
+/// share a number, so they end up with identical tags.
+pub fn frames(files: Vec<String>) -> Vec<(usize, String)> {
+    let mut frame = 0;
+    let mut previous: Option<PathBuf> = None;
+
+    files
+        .into_iter()
+        .map(|file| {
+            let key = frame_key(&file).0;
+            if previous.as_ref().is_some_and(|previous| *previous != key) {
+                frame += 1;
+            }
+            previous = Some(key);
+            (frame, file)
+        })
+        .collect()
+}
+
+/// Timestamp of each numbered file. Frames matching a range count up from the range's date and the
+/// others from `start`, each in steps of `interval` seconds. Timestamps too far in the future to
+/// compute are `None`.
+pub fn frame_times(
+    frames: &[(usize, String)],
+    start: DateTime,
+    ranges: &[FileRange],
+    interval: i64,
+) -> Vec<Option<DateTime>> {
+    // Frames seen so far per range, with `ranges.len()` for frames outside any range.
+    let mut counts = vec![0_i64; ranges.len() + 1];
+    let mut previous: Option<(usize, Option<DateTime>)> = None;
+
+    frames
+        .iter()
+        .map(|(frame, file)| {
+            if let Some((previous_frame, time)) = previous
+                && previous_frame == *frame
+            {
+                return time;
+            }
+
+            let range = ranges.iter().position(|range| range.contains(file));
+            let base = range.map_or(start, |range| ranges[range].start);
+            let count = &mut counts[range.unwrap_or(ranges.len())];
+            let time = count
+                .checked_mul(interval)
+                .and_then(|seconds| base.checked_add_seconds(seconds));
+            *count += 1;
+
+            previous = Some((*frame, time));
+            time
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(first: &str, last: &str, start: DateTime) -> FileRange {
+        FileRange {
+            first: first.to_string(),
+            last: last.to_string(),
+            start,
+        }
+    }
+
+    #[test]
+    fn parse_ranges() {
+        let cases = [
+            (
+                "IMG_0100..IMG_0250=2021-06-14",
+                0,
+                false,
+                Some(("IMG_0100", "IMG_0250", "2021:06:14 00:00:00")),
+            ),
+            (
+                "a=b..c=1999-01-01",
+                0,
+                false,
+                Some(("a=b", "c", "1999:01:01 00:00:00")),
+            ),
+            (
+                "a..b=@0",
+                3600,
+                false,
+                Some(("a", "b", "1970:01:01 01:00:00")),
+            ),
+            (
+                "a..b=2023-02-30",
+                0,
+                true,
+                Some(("a", "b", "2023:02:28 00:00:00")),
+            ),
+            ("a..b=2023-02-30", 0, false, None),
+            ("a..b=10000-01-01", 0, false, None),
+            ("a-b=1999-01-01", 0, false, None),
+            ("a..b", 0, false, None),
+            ("", 0, false, None),
+        ];
+        for (value, tz, clamp_day, expected) in cases {
+            let parsed = FileRange::parse(value, tz, clamp_day)
+                .map(|range| (range.first, range.last, range.start.to_string()));
+            let expected = expected.map(|(first, last, start)| {
+                (first.to_string(), last.to_string(), start.to_string())
+            });
+            assert_eq!(parsed, expected, "{}", value);
+        }
+    }
+
+    #[test]
+    fn ranges_contain_file_names_without_extension() {
+        let range = range("IMG_0100", "IMG_0250", DateTime::date(2021, 6, 14));
+        let cases = [
+            ("IMG_0100.jpg", true),
+            ("scans/IMG_0250.tif", true),
+            ("IMG_0180", true),
+            ("IMG_0099.jpg", false),
+            ("IMG_0251.jpg", false),
+            ("IMG_0250.jpg.xmp", false),
+            ("scans/IMG_0100/IMG_0001.jpg", false),
+            ("", false),
+        ];
+        for (file, expected) in cases {
+            assert_eq!(range.contains(file), expected, "{}", file);
+        }
+    }
+
+    #[test]
+    fn frames_share_numbers_by_name_without_extension() {
+        let cases: [(&[&str], &[usize]); 4] = [
+            (&[], &[]),
+            (&["a.jpg", "b.jpg", "c.jpg"], &[0, 1, 2]),
+            (
+                &["a.jpg", "a.tif", "b.jpg", "c.cr2", "c.jpg"],
+                &[0, 0, 1, 2, 2],
+            ),
+            (&["x/a.jpg", "y/a.jpg"], &[0, 1]),
+        ];
+        for (files, expected) in cases {
+            let files: Vec<String> = files.iter().map(|file| file.to_string()).collect();
+            let numbers: Vec<usize> = frames(files.clone()).into_iter().map(|(n, _)| n).collect();
+            assert_eq!(numbers, expected, "{:?}", files);
+        }
+    }
+
+    type FrameTimesCase<'a> = (&'a [&'a str], &'a [FileRange], i64, &'a [Option<&'a str>]);
+
+    #[test]
+    fn frame_times_count_per_range() {
+        let start = DateTime::date(1999, 1, 1);
+        let ranges = [range("b", "c", DateTime::date(2021, 6, 14))];
+        let cases: [FrameTimesCase; 4] = [
+            (
+                &["a.jpg", "b.jpg", "c.jpg", "d.jpg"],
+                &[],
+                1,
+                &[
+                    Some("1999:01:01 00:00:00"),
+                    Some("1999:01:01 00:00:01"),
+                    Some("1999:01:01 00:00:02"),
+                    Some("1999:01:01 00:00:03"),
+                ],
+            ),
+            (
+                &["a.jpg", "a.tif", "b.jpg"],
+                &[],
+                90,
+                &[
+                    Some("1999:01:01 00:00:00"),
+                    Some("1999:01:01 00:00:00"),
+                    Some("1999:01:01 00:01:30"),
+                ],
+            ),
+            (
+                &["a.jpg", "b.jpg", "c.jpg", "c.tif", "d.jpg"],
+                &ranges,
+                86_400,
+                &[
+                    Some("1999:01:01 00:00:00"),
+                    Some("2021:06:14 00:00:00"),
+                    Some("2021:06:15 00:00:00"),
+                    Some("2021:06:15 00:00:00"),
+                    Some("1999:01:02 00:00:00"),
+                ],
+            ),
+            (
+                &["a.jpg", "b.jpg"],
+                &[],
+                i64::MAX,
+                &[Some("1999:01:01 00:00:00"), None],
+            ),
+        ];
+        for (files, ranges, interval, expected) in cases {
+            let files: Vec<String> = files.iter().map(|file| file.to_string()).collect();
+            let times: Vec<Option<String>> = frame_times(&frames(files), start, ranges, interval)
+                .into_iter()
+                .map(|time| time.map(|time| time.to_string()))
+                .collect();
+            let expected: Vec<Option<String>> = expected
+                .iter()
+                .map(|time| time.map(str::to_string))
+                .collect();
+            assert_eq!(times, expected, "interval {}", interval);
+        }
+
+        // The third frame is two intervals in, which no longer fits an `i64`.
+        let files = vec![
+            "a.jpg".to_string(),
+            "b.jpg".to_string(),
+            "c.jpg".to_string(),
+        ];
+        let times = frame_times(&frames(files), start, &[], i64::MAX / 2 + 1);
+        assert!(times[1].is_some_and(|time| !time.is_writable()));
+        assert!(times[2].is_none());
+    }
+}
@@ -0,0 +1,14 @@
+//! Set EXIF metadata of scanned film negatives with exiftool.
+//!
+//! The `exif-film` command line tool is built on this library. [`ExifDateEditor`] reads and
+//! writes the dates of files, [`frames`] numbers the files of a roll and assigns them timestamps,
+//! and [`files`] finds the files to process.
+
+pub mod datetime;
+mod editor;
+mod exiftool;
+pub mod files;
+pub mod frames;
+pub mod output;
+
+pub use editor::{Applied, Changes, DateRead, DateTag, Error, ErrorKind, ExifDateEditor, Written};
@@ -1,95 +1,622 @@
+mod printer;
+
+use std::collections::HashMap;
 use std::env;
-use std::ffi::OsStr;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
+use exif_film::datetime::{self, DateTime, Precision};
+use exif_film::frames::{self, FileRange};
+use exif_film::output::{self, Format};
+use exif_film::{Changes, DateRead, DateTag, Error, ErrorKind, ExifDateEditor, files};
 use futures::future::join_all;
+use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::Command;
-use tokio::task::JoinHandle;
+use tokio::sync::{Mutex, Semaphore};
+use tokio::task::{JoinError, JoinHandle};
 
-/// Print usage and exit.
+/// Print usage to stderr and exit with an error.
 fn usage() -> ! {
-    eprintln!(
-        "Usage: exif-film <year>-<month>-<day> <film> <process> <camera> <lens> <file1> [file2 ...]\n\
-        \n\
-        <year>-<month>-<day>    Example: 1999-01-01\n\
-        <film>                  Type of film and ISO. Example: Ilford HP5+ @1600\n\
-        <process>               Film process. Example: Rodinal 1+25 @1600\n\
-        <camera>                Original camera\n\
-        <lens>                  Original lens\n\
-        <file…>                 One or more image files to modify\n\
-        \n\
-        The date will overwrite the `DateTimeOriginal` tag starting at time 00:00:00 and incremeting\n\
-        by 1 second in order of the filnames, while the rest of the fields will overwrite the\n\
-        `UserComment` tag separated by `;`. The `@` character is a convention and meant to be used as\n\
-        a marker that the following numeric token is an ISO identifier allowing you to provide\n\
-        \"shot at\" and \"processed at\" ISO values.\n\
-        \n\
-        Will also update the `DateTimeOriginal` in any correspodning `XMP` sidecar files. You may need\n\
-        to re-import your photos into which ever photo library you use afterwards."
-    );
+    eprintln!("{}", USAGE);
     std::process::exit(1);
 }
 
-/// Write EXIF tags using exiftool.
-async fn write_exif_tags<T>(
-    file: T,
-    date_time_original: &str,
-    user_comment: &str,
-) -> std::io::Result<()>
-where
-    T: AsRef<OsStr>,
-{
-    let mut cmd = Command::new("exiftool");
+/// The usage printed by `--help` and for invalid arguments.
+const USAGE: &str = "\
+Usage: exif-film [options] <year>-<month>-<day> <film> <process> <camera> <lens> [file1 ...]
+       exif-film restore [--backup=<suffix>] <file…>
 
-    cmd.arg("-overwrite_original")
-        .arg(format!("-DateTimeOriginal={}", date_time_original))
-        .arg(format!("-UserComment={}", user_comment))
-        .arg(file);
+<year>-<month>-<day>    Example: 1999-01-01. Years go from 1 to 9999, e.g. 800-05-01.
+                        Also accepts today, yesterday, and now or @<unix timestamp>
+                        to start at a given time
+<film>                  Type of film and ISO. Example: Ilford HP5+ @1600
+<process>               Film process. Example: Rodinal 1+25 @1600
+<camera>                Original camera
+<lens>                  Original lens
+<file…>                 One or more image files or directories of them to modify, unless
+                        given by --files-from
 
-    let status = cmd.status().await?;
+Options:
+-h, --help              Show this help
+--backup[=<suffix>]     Keep a copy of each file and its sidecar, named with the suffix,
+                        before writing. Restore them with `restore`. Defaults to
+                        _original
+--clamp-day             Use the last day of the month for dates past its end, like
+                        2023-02-30, instead of rejecting them
+--shard <i>/<n>         Only process the i-th of n shards of the file list. Example: 2/4
+-n, --dry-run           Show the current and new date of each file without
+                        writing anything
+--ext <extensions>      Only process files with these extensions from directories instead
+                        of the image and video formats exiftool can write. Example: jpg,tif
+--files-from <list>     Also process the files in a list, one per line, or read it from
+                        stdin if `-`. Accepts .m3u playlists and file:// URLs. Can be
+                        repeated
+-0, --null              The lists of --files-from are separated by NUL, as printed by
+                        `find -print0`
+--time <HH:MM:SS>       Start the frames at this time of day. Example: 14:30:00
+--hour, --minute, --second <n>
+                        Only replace the hour, minute or second of the start time
+--include-ifd0-datetime Also write the IFD0 `DateTime` tag shown by some viewers
+-i, --interactive       Show the change of each file and ask whether to make it. Answer
+                        y(es), n(o), a(ll) or q(uit)
+--i-know-what-im-doing  Also tag the files in directories of the system, like /usr or
+                        C:\\Windows, and of apps
+--interval <duration>   Time between successive frames. Example: 5s. Defaults to 1s
+--jobs <n>              Run at most n exiftool processes at once. Defaults to the number
+                        of CPUs
+--min-size <size>       Skip files smaller than the size. Example: 50KB
+--max-size <size>       Skip files larger than the size. Example: 2GB
+--output <format>       Print the result of each file as text or as one JSON object per
+                        line, or print the planned tags as exiftool-csv without writing
+                        them. Defaults to text
+--porcelain[=v1]        Print the result of each file in a stable, tab separated format
+--pre-hook <command>    Run a shell command before tagging, e.g. to take a snapshot
+--post-hook <command>   Run a shell command after tagging. Both get the details of the
+                        run in EXIF_FILM_* environment variables
+--precision <unit>      Record that the date is only known to the year, month or day in
+                        XMP `CreateDate`. Example: year
+--range <from>..<to>=<date>
+                        Use another date for the files whose name without extension is
+                        in the range. Example: IMG_0100..IMG_0250=2021-06-14. Can be
+                        repeated
+-r, --recursive         Also process the files in subdirectories of directories
+--serialize-per-dir     Write the files of a directory one at a time, while directories
+                        are processed in parallel
+--set-mtime             Also set the file modification time to the new date
+--strict                Also exit with an error if corrupt or unsupported files were
+                        skipped
+--tags <tags>           Date tags to write instead of `DateTimeOriginal`. Any of
+                        DateTimeOriginal, CreateDate, ModifyDate, XMP:CreateDate,
+                        IPTC:DateCreated and QuickTime:CreateDate. Example:
+                        DateTimeOriginal,CreateDate
+--timeout <duration>    Fail files that exiftool takes longer than this for. Example: 30s
+--tz <zone>             Timezone of the dates, utc or an offset like +02:00. Resolves
+                        now, today, yesterday and unix timestamps and is written to
+                        `OffsetTimeOriginal`. Defaults to utc without an offset tag
+--convert-tz <zone>     Shift the dates from --tz into this timezone. Example: +09:00
 
-    if status.success() {
-        Ok(())
-    } else {
-        Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "exiftool returned non‑zero status",
-        ))
+The date will overwrite the `DateTimeOriginal` tag starting at time 00:00:00 (or the current
+time, the time of the unix timestamp or the given --time) and incremeting by 1 second (or the
+interval) in order of the filnames, while the rest of the fields will overwrite the
+`UserComment` tag separated by `;`. The `@` character is a convention and meant to be used as a
+marker that the following numeric token is an ISO identifier allowing you to provide \"shot at\"
+and \"processed at\" ISO values. Files that only differ by extension are treated as the same
+frame and get identical tags.
+
+Will also update the `DateTimeOriginal` in any correspodning `XMP` sidecar files. You may need
+to re-import your photos into which ever photo library you use afterwards.";
+
+/// Explain why a date was rejected: it is past the end of its month, or doesn't match
+/// `expected`.
+fn invalid_date_hint(date: &str, expected: &str) -> String {
+    match datetime::parse_start(date, 0, true) {
+        Some(clamped) => format!(
+            "the month only has {} days. Use --clamp-day to write {:04}-{:02}-{:02} instead",
+            clamped.day, clamped.year, clamped.month, clamped.day
+        ),
+        None => format!("expected {}", expected),
     }
 }
 
-/// Update xmp file if it exists
-async fn update_xmp<T>(file: T, date_time_original: &str)
-where
-    T: AsRef<OsStr>,
-{
-    let mut cmd = Command::new("sed");
+/// Print an error and exit.
+fn fail(message: &str) -> ! {
+    eprintln!("Error: {}", message);
+    std::process::exit(1);
+}
 
-    let mut xmp = file.as_ref().to_os_string();
-    xmp.push(".xmp");
+/// Print a human-readable line, to stderr if stdout holds machine-readable output.
+fn log(options: &Options, message: &str) {
+    let line = format!("{}\n", message);
+    match options.output {
+        Format::Text => printer::stdout(line),
+        Format::Json | Format::ExiftoolCsv | Format::Porcelain => printer::stderr(line),
+    }
+}
+
+/// Suffix of backups made by `--backup` without a value, the same as exiftool's.
+const DEFAULT_BACKUP_SUFFIX: &str = "_original";
+
+/// Command line options.
+struct Options {
+    /// Copy files to the same name with this suffix before writing.
+    backup: Option<String>,
+    /// Use the last day of the month for dates past its end instead of rejecting them.
+    clamp_day: bool,
+    /// Show what would be written without changing any file.
+    dry_run: bool,
+    /// Only process files with these lowercase extensions when reading directories.
+    extensions: Vec<String>,
+    /// Shift the dates from `tz` into this timezone, in seconds east of UTC.
+    convert_tz: Option<i64>,
+    /// Lists of additional files to process.
+    files_from: Vec<String>,
+    /// The file lists are separated by NUL instead of line breaks.
+    null: bool,
+    /// Start the day at this hour instead of the hour of the date.
+    hour: Option<u32>,
+    /// Also write the IFD0 `DateTime` tag.
+    include_ifd0_datetime: bool,
+    /// Ask before changing each file.
+    interactive: bool,
+    /// Also tag the files in directories of the system and of apps.
+    allow_system_directories: bool,
+    /// Seconds between the timestamps of successive frames.
+    interval: i64,
+    /// Run at most this many exiftool processes at once instead of one per CPU.
+    jobs: Option<usize>,
+    /// Shell command run after all files are processed.
+    post_hook: Option<String>,
+    /// Shell command run before any file is processed.
+    pre_hook: Option<String>,
+    /// Start the day at this second instead of the second of the date.
+    second: Option<u32>,
+    /// Start the day at this time instead of the time of the date. Overridden by `hour`, `minute`
+    /// and `second`.
+    time: Option<(u32, u32, u32)>,
+    /// Only process the files of this shard.
+    shard: Option<Shard>,
+    /// Also exit with an error if files were skipped.
+    strict: bool,
+    /// Start the day at this minute instead of the minute of the date.
+    minute: Option<u32>,
+    /// Skip files smaller than this many bytes.
+    min_size: Option<u64>,
+    /// Skip files larger than this many bytes.
+    max_size: Option<u64>,
+    /// How the result of each file is printed.
+    output: Format,
+    /// `<first>..<last>=<date>` specifications of file name ranges with their own date.
+    ranges: Vec<String>,
+    /// Descend into subdirectories of directory arguments.
+    recursive: bool,
+    /// Also record the date in XMP `CreateDate` truncated to this precision.
+    precision: Option<Precision>,
+    /// Process the files of a directory one at a time.
+    serialize_per_dir: bool,
+    /// Set the file modification time to the new date.
+    set_mtime: bool,
+    /// Date tags to write instead of `DateTimeOriginal`.
+    tags: Option<Vec<DateTag>>,
+    /// Fail a file if exiftool takes longer than this many seconds.
+    timeout: Option<i64>,
+    /// Timezone used to resolve timestamps and relative dates, in seconds east of UTC. Defaults
+    /// to UTC when resolving dates.
+    tz: Option<i64>,
+}
 
-    let sed_status = cmd
-        .arg("-i")
-        .arg(format!(
-            "s/exif:DateTimeOriginal=\"[^\"]*\"/exif:DateTimeOriginal=\"{}\"/g",
-            date_time_original
+impl Options {
+    /// Replace the time of day of a start date as given by `--time`, `--hour`, `--minute` and
+    /// `--second`, then shift it into the `--convert-tz` timezone.
+    fn start_time(&self, start: DateTime) -> DateTime {
+        let start = match self.time {
+            Some((hour, minute, second)) => start.with_time(Some(hour), Some(minute), Some(second)),
+            None => start,
+        };
+        let start = start.with_time(self.hour, self.minute, self.second);
+
+        match self.convert_tz {
+            Some(offset) => start.add_seconds(offset - self.tz.unwrap_or(0)),
+            None => start,
+        }
+    }
+
+    /// The date tags to write to a file, the default for its format unless chosen with `--tags`.
+    fn date_tags(&self, file: &str) -> Vec<DateTag> {
+        let default = DateTag::default_for(file);
+        let mut tags = self.tags.clone().unwrap_or_else(|| vec![default]);
+        if self.include_ifd0_datetime && default == DateTag::DateTimeOriginal {
+            tags.push(DateTag::ModifyDate);
+        }
+
+        let mut unique = Vec::new();
+        for tag in tags {
+            if !unique.contains(&tag) {
+                unique.push(tag);
+            }
+        }
+        unique
+    }
+
+    /// Timezone of the written dates, if one was given.
+    fn offset(&self) -> Option<i64> {
+        self.convert_tz.or(self.tz)
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            backup: None,
+            clamp_day: false,
+            dry_run: false,
+            extensions: Vec::new(),
+            convert_tz: None,
+            files_from: Vec::new(),
+            null: false,
+            hour: None,
+            include_ifd0_datetime: false,
+            interactive: false,
+            allow_system_directories: false,
+            interval: 1,
+            jobs: None,
+            post_hook: None,
+            pre_hook: None,
+            second: None,
+            time: None,
+            shard: None,
+            strict: false,
+            minute: None,
+            min_size: None,
+            max_size: None,
+            output: Format::Text,
+            ranges: Vec::new(),
+            recursive: false,
+            precision: None,
+            serialize_per_dir: false,
+            set_mtime: false,
+            tags: None,
+            timeout: None,
+            tz: None,
+        }
+    }
+}
+
+/// One of `count` deterministic partitions of the file list, numbered from 1.
+#[derive(Clone, Copy)]
+struct Shard {
+    index: usize,
+    count: usize,
+}
+
+impl Shard {
+    /// Whether the file at `position` in the sorted file list belongs to this shard.
+    fn contains(&self, position: usize) -> bool {
+        position % self.count == self.index - 1
+    }
+}
+
+/// Parse a `<i>/<n>` shard specification.
+fn parse_shard(value: &str) -> Shard {
+    let shard = value
+        .split_once('/')
+        .and_then(|(index, count)| Some((index.parse().ok()?, count.parse().ok()?)));
+
+    match shard {
+        Some((index, count)) if index >= 1 && index <= count => Shard { index, count },
+        _ => fail(&format!(
+            "invalid shard `{}`, expected <i>/<n> with 1 <= i <= n",
+            value
+        )),
+    }
+}
+
+/// Parse a file size like `800`, `50KB` or `2GB`, in multiples of 1024 bytes.
+fn parse_size(value: &str) -> u64 {
+    let upper = value.to_ascii_uppercase();
+    let digits = upper.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let multiplier = match &upper[digits.len()..] {
+        "" | "B" => Some(1),
+        "K" | "KB" => Some(1 << 10),
+        "M" | "MB" => Some(1 << 20),
+        "G" | "GB" => Some(1 << 30),
+        _ => None,
+    };
+
+    let size = digits.trim().parse::<u64>().ok();
+    match size
+        .zip(multiplier)
+        .and_then(|(size, multiplier)| size.checked_mul(multiplier))
+    {
+        Some(size) => size,
+        None => fail(&format!(
+            "invalid size `{}`, expected a number with an optional KB, MB or GB unit",
+            value
+        )),
+    }
+}
+
+/// Whether a file is within the size limits of the options. Files whose size cannot be read
+/// are kept so that the error is reported when writing.
+fn within_size_limits(file: &str, options: &Options) -> bool {
+    if options.min_size.is_none() && options.max_size.is_none() {
+        return true;
+    }
+
+    let Ok(metadata) = std::fs::metadata(file) else {
+        return true;
+    };
+    let size = metadata.len();
+
+    options.min_size.is_none_or(|min| size >= min) && options.max_size.is_none_or(|max| size <= max)
+}
+
+/// Parse the value of an `--hour`, `--minute` or `--second` option.
+fn parse_time_part(name: &str, value: &str, max: u32) -> u32 {
+    datetime::parse_time_part(value, max).unwrap_or_else(|| {
+        fail(&format!(
+            "invalid {} `{}`, expected a number from 0 to {}",
+            name, value, max
         ))
-        .arg(&xmp)
-        .status()
-        .await;
+    })
+}
+
+/// Parse the value of a `--tz` or `--convert-tz` option.
+fn parse_offset(value: &str) -> i64 {
+    datetime::parse_offset(value).unwrap_or_else(|| {
+        fail(&format!(
+            "invalid timezone `{}`, expected utc or +HH:MM",
+            value
+        ))
+    })
+}
+
+/// Split the command line into options and positional arguments. Options may appear anywhere
+/// until a `--` argument, after which everything is positional.
+fn parse_args(args: impl IntoIterator<Item = String>) -> (Options, Vec<String>) {
+    let mut options = Options::default();
+    let mut positional = Vec::new();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            positional.extend(args.by_ref());
+            break;
+        }
+
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            "-n" => {
+                options.dry_run = true;
+                continue;
+            }
+            "-r" => {
+                options.recursive = true;
+                continue;
+            }
+            "-i" => {
+                options.interactive = true;
+                continue;
+            }
+            "-0" => {
+                options.null = true;
+                continue;
+            }
+            _ => {}
+        }
 
-    if let Some(_) = sed_status.ok() {
-        println!("Updated XMP: {}", xmp.display());
+        let Some(option) = arg.strip_prefix("--") else {
+            positional.push(arg);
+            continue;
+        };
+
+        let (name, inline) = match option.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (option, None),
+        };
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| args.next())
+                .unwrap_or_else(|| fail(&format!("missing value for `--{}`", name)))
+        };
+        let flag = || {
+            if inline.is_some() {
+                fail(&format!("`--{}` does not take a value", name));
+            }
+            true
+        };
+
+        match name {
+            "backup" => {
+                let suffix = inline.clone().unwrap_or(DEFAULT_BACKUP_SUFFIX.to_string());
+                if suffix.is_empty() {
+                    fail("the backup suffix must not be empty");
+                }
+                // Backups must not be picked up from directories as images or sidecars.
+                if let Some((_, extension)) = suffix.rsplit_once('.')
+                    && (extension.eq_ignore_ascii_case("xmp")
+                        || files::IMAGE_EXTENSIONS
+                            .contains(&extension.to_ascii_lowercase().as_str()))
+                {
+                    fail(&format!(
+                        "the backup suffix `{}` must not end in an image or sidecar extension",
+                        suffix
+                    ));
+                }
+                options.backup = Some(suffix);
+            }
+            "clamp-day" => options.clamp_day = flag(),
+            "second" => options.second = Some(parse_time_part(name, &value(), 59)),
+            "serialize-per-dir" => options.serialize_per_dir = flag(),
+            "set-mtime" => options.set_mtime = flag(),
+            "shard" => options.shard = Some(parse_shard(&value())),
+            "strict" => options.strict = flag(),
+            "precision" => {
+                let value = value();
+                options.precision = Some(Precision::parse(&value).unwrap_or_else(|| {
+                    fail(&format!(
+                        "invalid precision `{}`, expected year, month or day",
+                        value
+                    ))
+                }));
+            }
+            "dry-run" => options.dry_run = flag(),
+            "ext" => options.extensions.extend(
+                value()
+                    .split(',')
+                    .map(|extension| extension.trim_start_matches('.').to_ascii_lowercase()),
+            ),
+            "files-from" => options.files_from.push(value()),
+            "hour" => options.hour = Some(parse_time_part(name, &value(), 23)),
+            "include-ifd0-datetime" => options.include_ifd0_datetime = flag(),
+            "interactive" => options.interactive = flag(),
+            "i-know-what-im-doing" => options.allow_system_directories = flag(),
+            "null" => options.null = flag(),
+            "interval" => {
+                let value = value();
+                options.interval = datetime::parse_duration(&value).unwrap_or_else(|| {
+                    fail(&format!(
+                        "invalid interval `{}`, expected a duration like 5s, 2m or 1h",
+                        value
+                    ))
+                });
+            }
+            "jobs" => {
+                let value = value();
+                options.jobs = Some(value.parse().ok().filter(|jobs| *jobs > 0).unwrap_or_else(
+                    || {
+                        fail(&format!(
+                            "invalid jobs `{}`, expected a positive number",
+                            value
+                        ))
+                    },
+                ));
+            }
+            "minute" => options.minute = Some(parse_time_part(name, &value(), 59)),
+            "min-size" => options.min_size = Some(parse_size(&value())),
+            "max-size" => options.max_size = Some(parse_size(&value())),
+            "output" => {
+                let value = value();
+                options.output = Format::parse(&value).unwrap_or_else(|| {
+                    fail(&format!(
+                        "invalid output `{}`, expected text, json, exiftool-csv or porcelain-v1",
+                        value
+                    ))
+                });
+            }
+            "porcelain" => {
+                let version = inline.as_deref().unwrap_or("v1");
+                if version != "v1" {
+                    fail(&format!(
+                        "unknown porcelain version `{}`, expected v1",
+                        version
+                    ));
+                }
+                options.output = Format::Porcelain;
+            }
+            "post-hook" => options.post_hook = Some(value()),
+            "pre-hook" => options.pre_hook = Some(value()),
+            "range" => options.ranges.push(value()),
+            "recursive" => options.recursive = flag(),
+            "time" => {
+                let value = value();
+                let (hour, minute, second) = datetime::parse_time(&value).unwrap_or_else(|| {
+                    fail(&format!("invalid time `{}`, expected HH:MM:SS", value))
+                });
+                options.time = Some((hour, minute, second));
+            }
+            "convert-tz" => options.convert_tz = Some(parse_offset(&value())),
+            "tags" => options.tags = Some(parse_tags(&value())),
+            "timeout" => {
+                let value = value();
+                options.timeout = Some(datetime::parse_duration(&value).unwrap_or_else(|| {
+                    fail(&format!(
+                        "invalid timeout `{}`, expected a duration like 30s or 2m",
+                        value
+                    ))
+                }));
+            }
+            "tz" => options.tz = Some(parse_offset(&value())),
+            _ => fail(&format!("unknown option `--{}`", name)),
+        }
+    }
+
+    (options, positional)
+}
+
+/// Parse the comma separated date tags of `--tags`.
+fn parse_tags(value: &str) -> Vec<DateTag> {
+    value
+        .split(',')
+        .map(|tag| {
+            DateTag::parse(tag.trim()).unwrap_or_else(|| {
+                fail(&format!(
+                    "unknown tag `{}`, expected DateTimeOriginal, CreateDate, ModifyDate, \
+                    XMP:CreateDate, IPTC:DateCreated or QuickTime:CreateDate",
+                    tag
+                ))
+            })
+        })
+        .collect()
+}
+
+/// The changes made to one file. Its first tag is the one shown as the date before the change.
+fn changes(
+    file: &str,
+    date_time_original: DateTime,
+    user_comment: &str,
+    options: &Options,
+) -> Changes {
+    Changes {
+        date_time: date_time_original,
+        offset: options.offset(),
+        tags: options.date_tags(file),
+        precision: options.precision,
+        user_comment: Some(user_comment.to_string()),
+        set_mtime: options.set_mtime,
     }
 }
 
 #[tokio::main(flavor = "multi_thread")]
 async fn main() {
-    let args: Vec<String> = env::args().skip(1).collect();
-    if args.len() < 6 {
+    let (mut options, mut args) = parse_args(env::args().skip(1));
+    // Started by double-clicking or dropping a folder on it, so ask instead of printing usage.
+    if env::args().len() == 1 && std::io::stdin().is_terminal() {
+        args = wizard();
+        options.interactive = true;
+    }
+    // The CSV is applied with exiftool later, so nothing is written now.
+    if options.output == Format::ExiftoolCsv {
+        options.dry_run = true;
+    }
+    let options = Arc::new(options);
+
+    if args.first().is_some_and(|command| command == "restore") {
+        restore(&args[1..], &options).await;
+        return;
+    }
+    if args.len() < 5 || (args.len() == 5 && options.files_from.is_empty()) {
         usage();
     }
 
-    let date = args.get(0).unwrap().clone();
+    let date = args.first().unwrap();
+    let start = datetime::parse_start(date, options.tz.unwrap_or(0), options.clamp_day)
+        .unwrap_or_else(|| {
+            fail(&format!(
+                "invalid date `{}`, {}",
+                date,
+                invalid_date_hint(
+                    date,
+                    "<year>-<month>-<day>, @<unix timestamp>, now, today or yesterday"
+                )
+            ))
+        });
+    let start = options.start_time(start);
     let comment = {
         let film = args.get(1).unwrap();
         let process = args.get(2).unwrap();
@@ -99,55 +626,829 @@ async fn main() {
         format!("{};{};{};{}", film, process, camera, lens)
     };
 
-    let mut files = args[5..].to_vec();
-    files.sort_by(|a, b| a.cmp(b));
+    if options.interactive && options.files_from.iter().any(|list| list == "-") {
+        fail("--interactive reads the answers from stdin, so it can't be used with --files-from -");
+    }
+
+    let mut files = files::expand_globs(args[5..].to_vec());
+    for list in &options.files_from {
+        match files::read_file_list(list, options.null) {
+            Ok(listed) => files.extend(listed),
+            Err(err) => fail(&format!("could not read file list {}: {}", list, err)),
+        }
+    }
+    let mut files = expand_directories(files, &options).await;
+    files.retain(|file| !is_backup(file, &options) && within_size_limits(file, &options));
+    files.sort_by(|a, b| frames::frame_key(a).cmp(&frames::frame_key(b)));
 
-    let handles: Vec<JoinHandle<_>> = files
+    let ranges: Vec<FileRange> = options
+        .ranges
+        .iter()
+        .map(|range| {
+            FileRange::parse(range, options.tz.unwrap_or(0), options.clamp_day).unwrap_or_else(
+                || {
+                    let date = range.rsplit_once('=').map_or("", |(_, date)| date);
+                    fail(&format!(
+                        "invalid range `{}`, {}",
+                        range,
+                        invalid_date_hint(date, "<first>..<last>=<date>")
+                    ))
+                },
+            )
+        })
+        .map(|range| FileRange {
+            start: options.start_time(range.start),
+            ..range
+        })
+        .collect();
+
+    // Timestamps follow the position of the frame in the full file list so that shards combine
+    // into the same result as a single run.
+    let frames = frames::frames(files);
+    let times = frames::frame_times(&frames, start, &ranges, options.interval);
+    if let Some(((_, file), _)) = frames
+        .iter()
+        .zip(&times)
+        .find(|(_, time)| !time.is_some_and(|time| time.is_writable()))
+    {
+        fail(&format!(
+            "the timestamp of {} is past the year {}, EXIF dates have four digit years",
+            file,
+            datetime::YEARS.end()
+        ));
+    }
+    let jobs = options
+        .jobs
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |jobs| jobs.get()));
+    let timeout = options
+        .timeout
+        .map(|timeout| Duration::from_secs(timeout as u64));
+    let editor = Arc::new(ExifDateEditor::new(jobs, timeout));
+    if let Err(err) = editor.start().await {
+        fail(&err.to_string());
+    }
+    // Limits the files checked and written at once, which keep a file open while they wait for
+    // exiftool.
+    let permits = Arc::new(Semaphore::new(jobs));
+    let mut tasks: Vec<Task> = frames
         .into_iter()
-        .enumerate()
-        .map(|(i, file)| {
-            let original_date_time = format!("{} {}", date, seconds_to_time(i));
-            let comment = comment.clone();
+        .zip(times.into_iter().flatten())
+        .filter(|((i, _), _)| options.shard.is_none_or(|shard| shard.contains(*i)))
+        .map(|((_, file), date_time_original)| Task {
+            changes: changes(&file, date_time_original, &comment, &options),
+            file,
+        })
+        .collect();
+    if options.interactive {
+        tasks = confirm(tasks, &editor).await;
+    }
 
+    let run_id = run_id();
+    if let Some(hook) = &options.pre_hook {
+        let env = [
+            ("EXIF_FILM_HOOK", "pre".to_string()),
+            ("EXIF_FILM_RUN_ID", run_id.clone()),
+            ("EXIF_FILM_DRY_RUN", u8::from(options.dry_run).to_string()),
+            ("EXIF_FILM_FILES", tasks.len().to_string()),
+        ];
+        if let Err(err) = run_hook(hook, &env, &options).await {
+            fail(&format!("pre-hook failed: {}", err));
+        }
+    }
+
+    let printer = printer::start(tasks.len(), printer::available());
+
+    // One lock per directory, held while a file of the directory is processed.
+    let mut directories: HashMap<PathBuf, Arc<Mutex<()>>> = HashMap::new();
+    let handles: Vec<JoinHandle<_>> = tasks
+        .iter()
+        .map(|task| {
+            let Task { file, changes } = task.clone();
+            let editor = editor.clone();
+            let options = options.clone();
+            let permits = permits.clone();
+            let directory = options.serialize_per_dir.then(|| {
+                let directory = Path::new(&file).parent().unwrap_or(Path::new(""));
+                directories
+                    .entry(directory.to_path_buf())
+                    .or_default()
+                    .clone()
+            });
             tokio::spawn(async move {
-                match write_exif_tags(&file, &original_date_time, &comment).await {
-                    Ok(_) => {
-                        println!("OK: {}", file);
-                        update_xmp(&file, &original_date_time).await;
-                        Ok(())
-                    }
-                    Err(err) => {
-                        println!("Error: {}", err);
-                        Err(err)
-                    }
-                }
+                let _lock = match &directory {
+                    Some(directory) => Some(directory.lock().await),
+                    None => None,
+                };
+                let _permit = permits.acquire().await;
+                let report = process_file(&editor, &file, &changes, &options).await;
+                print_record(&options, &file, &changes, &report);
+                printer::done();
+                report
             })
         })
         .collect();
 
-    // Wait for all tasks to finish
-    let results = join_all(handles).await;
+    // Wait for all tasks to finish. A panicking task only loses its own file, which is reported
+    // as an internal error.
+    let reports: Vec<Report> = join_all(handles)
+        .await
+        .into_iter()
+        .zip(&tasks)
+        .map(|(result, Task { file, changes })| {
+            result.unwrap_or_else(|err| {
+                let message = format!("internal error: {}", panic_message(err));
+                log(&options, &format!("Error: {}: {}", file, message));
+                let report = Report {
+                    result: FileResult::Internal,
+                    size: None,
+                    original: None,
+                    read: None,
+                    error: Some(message),
+                };
+                print_record(&options, file, changes, &report);
+                printer::done();
+                report
+            })
+        })
+        .collect();
 
-    // Count successes
-    let success_count = results.iter().filter(|r| r.is_ok()).count();
-    let total_count = results.len();
+    editor.close().await;
+    printer.stop().await;
+
+    if options.output == Format::ExiftoolCsv {
+        let planned: Vec<(&str, Vec<String>)> = tasks
+            .iter()
+            .zip(&reports)
+            .filter(|(_, report)| matches!(report.result, FileResult::Planned))
+            .map(|(task, _)| (task.file.as_str(), task.changes.assignments()))
+            .collect();
+        print!("{}", output::exiftool_csv(&planned));
+    }
+
+    let results: Vec<FileResult> = reports.iter().map(|report| report.result).collect();
+    print_summary(&results, &options);
+
+    let skipped =
+        |result: &FileResult| matches!(result, FileResult::Corrupt | FileResult::Unsupported);
+    let mut failed = results
+        .iter()
+        .any(|result| result.is_failure() || (options.strict && skipped(result)));
+
+    if let Some(hook) = &options.post_hook {
+        let count = |matches: &dyn Fn(&FileResult) -> bool| {
+            results
+                .iter()
+                .filter(|result| matches(result))
+                .count()
+                .to_string()
+        };
+        let env = [
+            ("EXIF_FILM_HOOK", "post".to_string()),
+            ("EXIF_FILM_RUN_ID", run_id),
+            ("EXIF_FILM_DRY_RUN", u8::from(options.dry_run).to_string()),
+            ("EXIF_FILM_FILES", results.len().to_string()),
+            (
+                "EXIF_FILM_WRITTEN",
+                count(&|result| matches!(result, FileResult::Written(_) | FileResult::Planned)),
+            ),
+            ("EXIF_FILM_SKIPPED", count(&skipped)),
+            ("EXIF_FILM_FAILED", count(&FileResult::is_failure)),
+        ];
+        if let Err(err) = run_hook(hook, &env, &options).await {
+            log(&options, &format!("Error: post-hook failed: {}", err));
+            failed = true;
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+/// Replace the directories among `files` by the files in them and remove duplicates. Exits if a
+/// directory is refused or can't be read, and only warns about subdirectories that can't be read.
+async fn expand_directories(files: Vec<String>, options: &Options) -> Vec<String> {
+    let expanded = files::expand_directories(
+        files,
+        options.recursive,
+        &options.extensions,
+        options.allow_system_directories,
+    )
+    .await
+    .unwrap_or_else(|err| fail(&err.to_string()));
+    for (directory, err) in &expanded.unreadable {
+        log(
+            options,
+            &format!("Warning: skipped {}: {}", directory.display(), err),
+        );
+    }
+
+    // Files named several times would be written at the same time, which exiftool can't do.
+    files::remove_duplicates(expanded.files).await
+}
+
+/// Ask for each file whether to change it, showing the current and new date. Returns the tasks
+/// of the files to change.
+async fn confirm(tasks: Vec<Task>, editor: &ExifDateEditor) -> Vec<Task> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut confirmed = Vec::new();
+    let mut all = false;
 
-    println!("\n--- Summary ---");
-    println!(
-        "Successfully wrote tags to {} of {} files.",
-        success_count, total_count
+    for task in tasks {
+        if all {
+            confirmed.push(task);
+            continue;
+        }
+
+        // Like when writing, corrupt and unsupported files are not read by exiftool.
+        let current = match editor.check(&task.file).await {
+            Ok(_) => editor
+                .read_date(&task.file, task.changes.tags[0])
+                .await
+                .ok()
+                .and_then(|read| read.value)
+                .unwrap_or_else(|| "(none)".to_string()),
+            Err(err) => format!(
+                "({}: {})",
+                FileResult::from(err.kind()).label().to_lowercase(),
+                err
+            ),
+        };
+        loop {
+            eprint!(
+                "{}: {} → {} [y/n/a/q] ",
+                task.file, current, task.changes.date_time
+            );
+            // The end of input quits, like `q`.
+            let answer = lines.next_line().await.ok().flatten();
+            match answer.as_deref().map(str::trim) {
+                Some("y") => confirmed.push(task),
+                Some("n") => {}
+                Some("a") => {
+                    all = true;
+                    confirmed.push(task);
+                }
+                Some("q") | None => return confirmed,
+                Some(_) => continue,
+            }
+            break;
+        }
+    }
+
+    confirmed
+}
+
+/// Ask for the date, the roll details and the folder of scans. The changes are then confirmed
+/// file by file like with `--interactive`.
+fn wizard() -> Vec<String> {
+    eprintln!("No arguments given. Answer the questions below to tag a folder of scans, or press");
+    eprintln!("Ctrl-C to quit. Run exif-film --help to see all options.");
+    eprintln!();
+
+    let folder = ask(
+        "Folder with the scans (drag it here)",
+        |folder| match Path::new(folder) {
+            path if path.is_dir() => Ok(()),
+            path if path.exists() => Err("Not a folder"),
+            _ => Err("No such folder"),
+        },
     );
+    let date = ask("Date of the first frame, e.g. 1999-01-01", |date| {
+        datetime::parse_start(date, 0, false)
+            .map(|_| ())
+            .ok_or("Not a valid date between 1-01-01 and 9999-12-31")
+    });
+    let film = ask("Film and ISO, e.g. Ilford HP5+ @1600", |_| Ok(()));
+    let process = ask("Film process, e.g. Rodinal 1+25 @1600", |_| Ok(()));
+    let camera = ask("Camera", |_| Ok(()));
+    let lens = ask("Lens", |_| Ok(()));
+    eprintln!();
+
+    vec![date, film, process, camera, lens, folder]
 }
 
-fn seconds_to_time(total_seconds: usize) -> String {
-    // Ensure we don't exceed 24 hours (86400 seconds)
-    // If the input represents seconds past midnight, we usually want to wrap around
-    let seconds_in_day = total_seconds % 86_400;
+/// Ask a question on the terminal until `check` accepts the answer, printing why it didn't.
+/// Exits at the end of input.
+fn ask(question: &str, check: impl Fn(&str) -> Result<(), &'static str>) -> String {
+    let mut line = String::new();
+
+    loop {
+        eprint!("{}: ", question);
+        line.clear();
+        if std::io::stdin().read_line(&mut line).unwrap_or(0) == 0 {
+            std::process::exit(1);
+        }
 
-    let hours = seconds_in_day / 3600;
-    let remaining = seconds_in_day % 3600;
-    let minutes = remaining / 60;
-    let seconds = remaining % 60;
+        let answer = answer(&line);
+        let checked = if answer.is_empty() {
+            Err("An answer is needed")
+        } else {
+            check(&answer)
+        };
+        match checked {
+            Ok(()) => return answer,
+            Err(reason) => eprintln!("{}, please try again.", reason),
+        }
+    }
+}
 
-    format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
+/// An answer typed or dragged into the terminal, without the surrounding whitespace, and
+/// without the quotes or, outside Windows, the backslash escapes that terminals and file
+/// managers add to dragged paths, as in `/scans/roll\ 1 `.
+fn answer(line: &str) -> String {
+    let line = line.trim_start().trim_end_matches(['\n', '\r']);
+    let mut answer = line.trim_end();
+    // Keep a trailing space that a backslash escapes.
+    if !cfg!(windows) && answer.ends_with('\\') && answer.len() < line.len() {
+        answer = &line[..answer.len() + 1];
+    }
+
+    if let Some(unquoted) = ['"', '\'']
+        .iter()
+        .find_map(|quote| answer.strip_prefix(*quote)?.strip_suffix(*quote))
+    {
+        return unquoted.to_string();
+    }
+    if cfg!(windows) {
+        return answer.to_string();
+    }
+
+    let mut unescaped = String::with_capacity(answer.len());
+    let mut chars = answer.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            c => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+/// Identifier of this run passed to the hooks, from the start time and process id.
+fn run_id() -> String {
+    let started = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    format!("{}-{}", started.as_secs(), std::process::id())
+}
+
+/// Run a hook with the shell, passing the run details in the environment. Its output is printed
+/// like the other messages, and a non-zero exit status is an error.
+async fn run_hook(hook: &str, env: &[(&str, String)], options: &Options) -> std::io::Result<()> {
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.arg("/C");
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.arg("-c");
+        command
+    };
+    let output = command
+        .arg(hook)
+        .envs(env.iter().map(|(name, value)| (name, value)))
+        .output()
+        .await?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for line in stdout.lines() {
+        log(options, line);
+    }
+    printer::stderr(String::from_utf8_lossy(&output.stderr).into_owned());
+
+    match output.status.code() {
+        _ if output.status.success() => Ok(()),
+        Some(code) => Err(std::io::Error::other(format!(
+            "`{}` exited with status {}",
+            hook, code
+        ))),
+        None => Err(std::io::Error::other(format!("`{}` was killed", hook))),
+    }
+}
+
+/// Restore files from the backups made by `--backup`.
+async fn restore(args: &[String], options: &Options) {
+    if args.is_empty() {
+        usage();
+    }
+
+    let suffix = options.backup.as_deref().unwrap_or(DEFAULT_BACKUP_SUFFIX);
+    let files = files::expand_globs(args.to_vec());
+    let mut files = expand_directories(files, options).await;
+    files.retain(|file| !is_backup(file, options));
+    files.sort();
+
+    let mut restored = 0;
+    let mut failed = false;
+    for file in &files {
+        match files::restore(file, suffix).await {
+            Ok(true) => {
+                log(options, &format!("Restored: {}", file));
+                restored += 1;
+            }
+            Ok(false) => log(options, &format!("No backup: {}", file)),
+            Err(err) => {
+                log(options, &format!("Error: {}: {}", file, err));
+                failed = true;
+            }
+        }
+    }
+
+    log(options, "\n--- Summary ---");
+    log(
+        options,
+        &format!("Restored {} of {} files.", restored, files.len()),
+    );
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+/// Whether a file is a backup made by `--backup`, which is never tagged itself: its name is that
+/// of an existing file with the default or the given suffix appended.
+fn is_backup(file: &str, options: &Options) -> bool {
+    std::iter::once(DEFAULT_BACKUP_SUFFIX)
+        .chain(options.backup.as_deref())
+        .filter_map(|suffix| file.strip_suffix(suffix))
+        .any(|original| !original.is_empty() && Path::new(original).is_file())
+}
+
+/// Check, tag and update the sidecar of one file, or only show the change for a dry run.
+async fn process_file(
+    editor: &ExifDateEditor,
+    file: &str,
+    changes: &Changes,
+    options: &Options,
+) -> Report {
+    let size = tokio::fs::metadata(file)
+        .await
+        .ok()
+        .map(|metadata| metadata.len());
+    let failed = |err: Error, read: Option<DateRead>| {
+        let result = FileResult::from(err.kind());
+        log(options, &format!("{}: {}: {}", result.label(), file, err));
+        Report {
+            result,
+            size,
+            original: read.as_ref().and_then(|read| read.value.clone()),
+            read: read.map(|read| read.elapsed),
+            error: Some(err.to_string()),
+        }
+    };
+
+    // Corrupt and unsupported files are never passed to exiftool, not even to read them.
+    match editor.check(file).await {
+        Ok(Some(warning)) => log(options, &format!("Warning: {}: {}", file, warning)),
+        Ok(None) => {}
+        Err(err) => return failed(err, None),
+    }
+
+    if options.dry_run {
+        return match editor.read_date(file, changes.tags[0]).await {
+            Ok(current) => {
+                log(
+                    options,
+                    &format!(
+                        "Would write: {}: {} → {}",
+                        file,
+                        current.value.as_deref().unwrap_or("(none)"),
+                        changes.date_time
+                    ),
+                );
+                Report {
+                    result: FileResult::Planned,
+                    size,
+                    original: current.value,
+                    read: Some(current.elapsed),
+                    error: None,
+                }
+            }
+            Err(err) => failed(err, None),
+        };
+    }
+
+    // Only the JSON and porcelain output show the date that is replaced.
+    let read = match options.output {
+        Format::Json | Format::Porcelain => editor.read_date(file, changes.tags[0]).await.ok(),
+        Format::Text | Format::ExiftoolCsv => None,
+    };
+
+    // The steps of `apply` without its check, which was done above.
+    if let Some(suffix) = &options.backup
+        && let Err(err) = editor.back_up(file, suffix).await
+    {
+        return failed(err, read);
+    }
+    let written = match editor.write(file, changes).await {
+        Ok(written) => written,
+        Err(err) => return failed(err, read),
+    };
+    printer::stderr(written.exiftool_warnings);
+    log(options, &format!("OK: {}", file));
+    // The file is written, so a sidecar that can't be updated is only a warning.
+    match editor.update_sidecar(file, changes.date_time).await {
+        Ok(Some(xmp)) => log(options, &format!("Updated XMP: {}", xmp.display())),
+        Ok(None) => {}
+        Err(err) => log(options, &format!("Warning: {}: {}", file, err)),
+    }
+
+    Report {
+        result: FileResult::Written(written.elapsed),
+        size,
+        original: read.as_ref().and_then(|read| read.value.clone()),
+        read: read.map(|read| read.elapsed),
+        error: None,
+    }
+}
+
+/// Print the JSON or porcelain record of a file as soon as it is done, so that the records of
+/// finished files survive an interrupted run.
+fn print_record(options: &Options, file: &str, changes: &Changes, report: &Report) {
+    let new = changes.date_time.to_string();
+    let record = match options.output {
+        Format::Json => output::json(&output::Record {
+            path: file,
+            original: report.original.as_deref(),
+            new: &new,
+            status: report.result.status(),
+            error: report.error.as_deref(),
+            size: report.size,
+            read: report.read,
+            write: match report.result {
+                FileResult::Written(elapsed) => Some(elapsed),
+                _ => None,
+            },
+        }),
+        Format::Porcelain => output::porcelain(
+            report.result.status(),
+            file,
+            report.original.as_deref(),
+            &new,
+        ),
+        Format::Text | Format::ExiftoolCsv => return,
+    };
+
+    printer::stdout(format!("{}\n", record));
+}
+
+/// Print the counts of each outcome and the exiftool latency.
+fn print_summary(results: &[FileResult], options: &Options) {
+    let count = |matches: fn(&FileResult) -> bool| results.iter().filter(|r| matches(r)).count();
+
+    let mut latencies: Vec<Duration> = results
+        .iter()
+        .filter_map(|r| match r {
+            FileResult::Written(elapsed) => Some(*elapsed),
+            _ => None,
+        })
+        .collect();
+    let corrupt_count = count(|r| matches!(r, FileResult::Corrupt));
+    let unsupported_count = count(|r| matches!(r, FileResult::Unsupported));
+    let internal_count = count(|r| matches!(r, FileResult::Internal));
+    let permission_count = count(|r| matches!(r, FileResult::PermissionDenied));
+    let disk_full_count = count(|r| matches!(r, FileResult::DiskFull));
+    let failed_count = count(|r| matches!(r, FileResult::Failed));
+    let total_count = results.len();
+
+    log(options, "\n--- Summary ---");
+    if options.dry_run {
+        log(
+            options,
+            &format!(
+                "Would write tags to {} of {} files.",
+                count(|r| matches!(r, FileResult::Planned)),
+                total_count
+            ),
+        );
+    } else {
+        log(
+            options,
+            &format!(
+                "Successfully wrote tags to {} of {} files.",
+                latencies.len(),
+                total_count
+            ),
+        );
+    }
+    if corrupt_count > 0 {
+        log(
+            options,
+            &format!("Skipped {} empty or corrupt files.", corrupt_count),
+        );
+    }
+    if unsupported_count > 0 {
+        log(
+            options,
+            &format!(
+                "Skipped {} files in formats exiftool can't write.",
+                unsupported_count
+            ),
+        );
+    }
+    if permission_count > 0 {
+        log(
+            options,
+            &format!(
+                "{} files failed without permission to write.",
+                permission_count
+            ),
+        );
+    }
+    if disk_full_count > 0 {
+        log(
+            options,
+            &format!("{} files failed because the disk is full.", disk_full_count),
+        );
+    }
+    if failed_count > 0 {
+        log(options, &format!("{} files failed.", failed_count));
+    }
+    if internal_count > 0 {
+        log(
+            options,
+            &format!("{} files failed with an internal error.", internal_count),
+        );
+    }
+
+    if !latencies.is_empty() {
+        latencies.sort();
+        log(
+            options,
+            &format!(
+                "exiftool latency: p50 {} ms, p95 {} ms",
+                percentile(&latencies, 50).as_millis(),
+                percentile(&latencies, 95).as_millis()
+            ),
+        );
+    }
+}
+
+/// A file to process with the changes it gets.
+#[derive(Clone)]
+struct Task {
+    file: String,
+    changes: Changes,
+}
+
+/// Outcome of processing a single file.
+#[derive(Clone, Copy)]
+enum FileResult {
+    /// Tags were written, with the time exiftool took.
+    Written(Duration),
+    /// Tags would be written, in a dry run.
+    Planned,
+    /// The file is empty or corrupt and was not passed to exiftool.
+    Corrupt,
+    /// exiftool can't write this format.
+    Unsupported,
+    /// The file or its directory can't be written.
+    PermissionDenied,
+    /// The disk ran out of space while writing.
+    DiskFull,
+    /// exiftool failed to write the tags.
+    Failed,
+    /// Processing the file panicked.
+    Internal,
+}
+
+impl FileResult {
+    /// Name of the outcome in the JSON output.
+    fn status(&self) -> &'static str {
+        match self {
+            FileResult::Written(_) => "written",
+            FileResult::Planned => "planned",
+            FileResult::Corrupt => "corrupt",
+            FileResult::Unsupported => "unsupported",
+            FileResult::PermissionDenied => "permission-denied",
+            FileResult::DiskFull => "disk-full",
+            FileResult::Failed => "failed",
+            FileResult::Internal => "internal",
+        }
+    }
+
+    /// Whether the file should have been written but wasn't.
+    fn is_failure(&self) -> bool {
+        matches!(
+            self,
+            FileResult::PermissionDenied
+                | FileResult::DiskFull
+                | FileResult::Failed
+                | FileResult::Internal
+        )
+    }
+
+    /// Prefix of the message printed for a file that wasn't written.
+    fn label(&self) -> &'static str {
+        match self {
+            FileResult::Corrupt => "Corrupt",
+            FileResult::Unsupported => "Unsupported",
+            FileResult::PermissionDenied => "Permission denied",
+            FileResult::DiskFull => "Disk full",
+            _ => "Error",
+        }
+    }
+}
+
+/// Outcome of a failed file by the cause of its error.
+impl From<ErrorKind> for FileResult {
+    fn from(kind: ErrorKind) -> Self {
+        match kind {
+            ErrorKind::Corrupt => FileResult::Corrupt,
+            ErrorKind::Unsupported => FileResult::Unsupported,
+            ErrorKind::PermissionDenied => FileResult::PermissionDenied,
+            ErrorKind::DiskFull => FileResult::DiskFull,
+            ErrorKind::TimedOut | ErrorKind::Other => FileResult::Failed,
+        }
+    }
+}
+
+/// Outcome of a file with the details shown in the JSON output.
+struct Report {
+    result: FileResult,
+    /// Size of the file in bytes, if it could be read.
+    size: Option<u64>,
+    /// The first written tag before the change, if it was read.
+    original: Option<String>,
+    /// Time exiftool took to read the original tag.
+    read: Option<Duration>,
+    /// Why the file was skipped or failed.
+    error: Option<String>,
+}
+
+/// The message of a panicked or cancelled task.
+fn panic_message(err: JoinError) -> String {
+    match err.try_into_panic() {
+        Ok(panic) => match panic.downcast::<String>() {
+            Ok(message) => *message,
+            Err(panic) => match panic.downcast::<&str>() {
+                Ok(message) => message.to_string(),
+                Err(_) => "panic".to_string(),
+            },
+        },
+        Err(err) => err.to_string(),
+    }
+}
+
+/// Nearest-rank percentile of sorted, non-empty durations.
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    let rank = (sorted.len() * percent).div_ceil(100);
+    sorted[rank.saturating_sub(1)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shards_partition_the_file_list() {
+        let cases = [
+            (1, 1, vec![0, 1, 2, 3, 4, 5]),
+            (1, 3, vec![0, 3]),
+            (2, 3, vec![1, 4]),
+            (3, 3, vec![2, 5]),
+            (7, 7, vec![]),
+        ];
+        for (index, count, expected) in cases {
+            let shard = Shard { index, count };
+            let positions: Vec<usize> = (0..6).filter(|p| shard.contains(*p)).collect();
+            assert_eq!(positions, expected, "{}/{}", index, count);
+        }
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn dragged_answers() {
+        let cases = [
+            ("1999-01-01\n", "1999-01-01"),
+            ("  Ilford HP5+ @1600 \r\n", "Ilford HP5+ @1600"),
+            ("/scans/roll\\ 1 \n", "/scans/roll 1"),
+            ("/scans/roll\\ \n", "/scans/roll "),
+            ("/scans/it\\'s\\ here\n", "/scans/it's here"),
+            ("'/scans/roll 1'\n", "/scans/roll 1"),
+            ("\"/scans/roll 1\"\n", "/scans/roll 1"),
+            ("'/scans/a\\b'\n", "/scans/a\\b"),
+            ("\n", ""),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(answer(line), expected, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn sizes() {
+        let cases = [
+            ("0", 0),
+            ("512", 512),
+            ("512B", 512),
+            ("50KB", 50 << 10),
+            ("50kb", 50 << 10),
+            ("50K", 50 << 10),
+            ("2MB", 2 << 20),
+            ("2GB", 2 << 30),
+            ("2g", 2 << 30),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(parse_size(value), expected, "{}", value);
+        }
+    }
 }
@@ -0,0 +1,274 @@
+use std::fmt::Write;
+use std::time::Duration;
+
+/// How the results of each file are printed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Human-readable lines on stdout.
+    Text,
+    /// One JSON object per file on stdout, with the human-readable lines on stderr.
+    Json,
+    /// The planned tags as a CSV file for `exiftool -csv=`, with the human-readable lines on
+    /// stderr.
+    ExiftoolCsv,
+    /// Version 1 of the tab separated records of `--porcelain`, with the human-readable lines on
+    /// stderr.
+    Porcelain,
+}
+
+impl Format {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "text" => Some(Format::Text),
+            "json" => Some(Format::Json),
+            "exiftool-csv" => Some(Format::ExiftoolCsv),
+            "porcelain-v1" => Some(Format::Porcelain),
+            _ => None,
+        }
+    }
+}
+
+/// The result of one file for [`json`].
+pub struct Record<'a> {
+    pub path: &'a str,
+    pub original: Option<&'a str>,
+    pub new: &'a str,
+    pub status: &'a str,
+    pub error: Option<&'a str>,
+    /// Size of the file in bytes.
+    pub size: Option<u64>,
+    /// Time exiftool took to read the original date.
+    pub read: Option<Duration>,
+    /// Time exiftool took to write the file.
+    pub write: Option<Duration>,
+}
+
+/// The result of one file as a single line JSON object, with `null` for unknown values.
+pub fn json(record: &Record) -> String {
+    let millis = |elapsed: Option<Duration>| {
+        elapsed.map_or("null".to_string(), |d| d.as_millis().to_string())
+    };
+    format!(
+        "{{\"path\":{},\"original\":{},\"new\":{},\"status\":{},\"error\":{},\"size\":{},\"read_ms\":{},\"write_ms\":{}}}",
+        json_string(record.path),
+        record.original.map_or("null".to_string(), json_string),
+        json_string(record.new),
+        json_string(record.status),
+        record.error.map_or("null".to_string(), json_string),
+        record
+            .size
+            .map_or("null".to_string(), |size| size.to_string()),
+        millis(record.read),
+        millis(record.write)
+    )
+}
+
+/// The result of one file as a `--porcelain=v1` record: status, path, previous and new date,
+/// separated by tabs. An unknown previous date is `-`, and tabs, line breaks and backslashes in
+/// the fields are escaped as `\t`, `\n`, `\r` and `\\`.
+///
+/// The format must not change, new information only goes into a new version.
+pub fn porcelain(status: &str, path: &str, original: Option<&str>, new: &str) -> String {
+    let escape = |field: &str| {
+        field
+            .replace('\\', "\\\\")
+            .replace('\t', "\\t")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r")
+    };
+
+    [status, path, original.unwrap_or("-"), new]
+        .map(escape)
+        .join("\t")
+}
+
+/// A CSV file in the format read by `exiftool -csv=`, with a `SourceFile` column and a column
+/// for each tag. The tags are exiftool assignments like `-DateTimeOriginal=...`. Cells of tags
+/// that a file doesn't get are left empty, which exiftool skips.
+pub fn exiftool_csv(files: &[(&str, Vec<String>)]) -> String {
+    let columns = |tags: &[String]| -> Vec<(String, String)> {
+        tags.iter()
+            .map(|tag| {
+                let (name, value) = tag
+                    .trim_start_matches('-')
+                    .split_once('=')
+                    .unwrap_or((tag, ""));
+                (name.to_string(), value.to_string())
+            })
+            .collect()
+    };
+
+    let mut csv = String::new();
+    if files.is_empty() {
+        return csv;
+    }
+
+    let mut header: Vec<String> = Vec::new();
+    for (_, tags) in files {
+        for (name, _) in columns(tags) {
+            if !header.contains(&name) {
+                header.push(name);
+            }
+        }
+    }
+    csv.push_str("SourceFile");
+    for name in &header {
+        csv.push(',');
+        csv.push_str(&csv_field(name));
+    }
+    csv.push('\n');
+
+    for (file, tags) in files {
+        let values = columns(tags);
+        csv.push_str(&csv_field(file));
+        for name in &header {
+            let value = values
+                .iter()
+                .find(|(column, _)| column == name)
+                .map_or("", |(_, value)| value);
+            csv.push(',');
+            csv.push_str(&csv_field(value));
+        }
+        csv.push('\n');
+    }
+
+    csv
+}
+
+/// Quote a CSV field if it contains separators, quotes or surrounding whitespace.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) || value.trim() != value {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Quote and escape a JSON string.
+fn json_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_strings() {
+        let cases = [
+            ("scan01.tif", r#""scan01.tif""#),
+            ("", r#""""#),
+            (r#"say "hi""#, r#""say \"hi\"""#),
+            (r"C:\scans", r#""C:\\scans""#),
+            ("a\nb\rc\td", r#""a\nb\rc\td""#),
+            ("\u{1}\u{1f}\u{7f}", r#""\u0001\u001f\u007f""#),
+            ("café", r#""café""#),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(json_string(value), expected, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn csv_fields() {
+        let cases = [
+            ("scan01.tif", "scan01.tif"),
+            ("", ""),
+            ("a,b", r#""a,b""#),
+            (r#"say "hi""#, r#""say ""hi""""#),
+            ("a\nb", "\"a\nb\""),
+            ("a\rb", "\"a\rb\""),
+            (" lens", r#"" lens""#),
+            ("lens ", r#""lens ""#),
+            ("Ilford HP5+ @1600", "Ilford HP5+ @1600"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(csv_field(value), expected, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn porcelain_records() {
+        let cases = [
+            (
+                (
+                    "written",
+                    "scan01.tif",
+                    Some("2001:02:03 04:05:06"),
+                    "1999:01:01 00:00:00",
+                ),
+                "written\tscan01.tif\t2001:02:03 04:05:06\t1999:01:01 00:00:00",
+            ),
+            (
+                ("planned", "scan01.tif", None, "1999:01:01 00:00:00"),
+                "planned\tscan01.tif\t-\t1999:01:01 00:00:00",
+            ),
+            (
+                ("failed", "a\tb\nc\rd\\e.jpg", None, "1999:01:01 00:00:00"),
+                "failed\ta\\tb\\nc\\rd\\\\e.jpg\t-\t1999:01:01 00:00:00",
+            ),
+        ];
+        for ((status, path, original, new), expected) in cases {
+            assert_eq!(porcelain(status, path, original, new), expected, "{}", path);
+        }
+    }
+
+    #[test]
+    fn exiftool_csv_files() {
+        let tags = |tags: &[&str]| tags.iter().map(|tag| tag.to_string()).collect::<Vec<_>>();
+        let cases = [
+            (vec![], ""),
+            (
+                vec![(
+                    "scan01.tif",
+                    tags(&[
+                        "-DateTimeOriginal=1999:01:01 00:00:00",
+                        "-UserComment=F;P;C;L",
+                    ]),
+                )],
+                "SourceFile,DateTimeOriginal,UserComment\n\
+                 scan01.tif,1999:01:01 00:00:00,F;P;C;L\n",
+            ),
+            (
+                vec![
+                    ("a.jpg", tags(&["-DateTimeOriginal=1999:01:01 00:00:00"])),
+                    (
+                        "b,c.mp4",
+                        tags(&["-QuickTime:CreateDate=1999:01:01 00:00:01+00:00"]),
+                    ),
+                    (
+                        "d.jpg",
+                        tags(&[
+                            "-DateTimeOriginal=1999:01:01 00:00:02",
+                            "-UserComment=Ilford \"HP5+\", Rodinal",
+                        ]),
+                    ),
+                ],
+                "SourceFile,DateTimeOriginal,QuickTime:CreateDate,UserComment\n\
+                 a.jpg,1999:01:01 00:00:00,,\n\
+                 \"b,c.mp4\",,1999:01:01 00:00:01+00:00,\n\
+                 d.jpg,1999:01:01 00:00:02,,\"Ilford \"\"HP5+\"\", Rodinal\"\n",
+            ),
+        ];
+        for (files, expected) in cases {
+            let files: Vec<(&str, Vec<String>)> = files;
+            assert_eq!(exiftool_csv(&files), expected);
+        }
+    }
+}
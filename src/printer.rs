@@ -0,0 +1,157 @@
+use std::io::{IsTerminal, Write};
+use std::sync::{Mutex, PoisonError};
+use std::time::Instant;
+
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+
+const WIDTH: usize = 30;
+
+/// Output sent to the printer task.
+enum Message {
+    Stdout(String),
+    Stderr(String),
+    /// A file is done, which advances the progress line.
+    Done,
+}
+
+/// The channel to the running printer task, if any.
+static PRINTER: Mutex<Option<UnboundedSender<Message>>> = Mutex::new(None);
+
+/// Whether a progress line can be drawn, only when both stdout and stderr are terminals. It is
+/// never drawn on Windows, where older consoles print the escape codes that redraw it.
+pub fn available() -> bool {
+    !cfg!(windows) && std::io::stdout().is_terminal() && std::io::stderr().is_terminal()
+}
+
+/// The task that owns stdout, stderr and the progress line while files are processed, so that
+/// lines printed from several tasks at once never mix with the progress line or each other.
+pub struct Printer(JoinHandle<()>);
+
+/// Print everything through one task until [`Printer::stop`], with a progress line for `total`
+/// files if `progress`.
+pub fn start(total: usize, progress: bool) -> Printer {
+    let (sender, messages) = mpsc::unbounded_channel();
+    *PRINTER.lock().unwrap_or_else(PoisonError::into_inner) = Some(sender);
+
+    Printer(tokio::spawn(run(messages, total, progress)))
+}
+
+impl Printer {
+    /// Print what is left, remove the progress line and print directly again.
+    pub async fn stop(self) {
+        PRINTER
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        let _ = self.0.await;
+    }
+}
+
+/// Print text, including its line breaks, to stdout.
+pub fn stdout(text: String) {
+    send(Message::Stdout(text));
+}
+
+/// Print text, including its line breaks, to stderr.
+pub fn stderr(text: String) {
+    send(Message::Stderr(text));
+}
+
+/// Count a file as done on the progress line.
+pub fn done() {
+    send(Message::Done);
+}
+
+/// Send a message to the printer task, or print it directly when none is running.
+fn send(message: Message) {
+    if let Message::Stdout(text) | Message::Stderr(text) = &message
+        && text.is_empty()
+    {
+        return;
+    }
+
+    let printer = PRINTER.lock().unwrap_or_else(PoisonError::into_inner);
+    let message = match printer.as_ref() {
+        Some(sender) => match sender.send(message) {
+            Ok(()) => return,
+            Err(err) => err.0,
+        },
+        None => message,
+    };
+    drop(printer);
+
+    write(message);
+}
+
+async fn run(mut messages: UnboundedReceiver<Message>, total: usize, progress: bool) {
+    let started = Instant::now();
+    let mut count = 0;
+
+    if progress {
+        draw(count, total, started);
+    }
+    while let Some(message) = messages.recv().await {
+        if progress {
+            clear();
+        }
+        if let Message::Done = message {
+            count += 1;
+        }
+        write(message);
+        if progress {
+            draw(count, total, started);
+        }
+    }
+
+    if progress {
+        clear();
+    }
+}
+
+fn write(message: Message) {
+    match message {
+        Message::Stdout(text) => {
+            let mut stdout = std::io::stdout().lock();
+            let _ = stdout.write_all(text.as_bytes());
+            let _ = stdout.flush();
+        }
+        Message::Stderr(text) => {
+            let _ = std::io::stderr().lock().write_all(text.as_bytes());
+        }
+        Message::Done => {}
+    }
+}
+
+/// Remove the progress line so that a message can be printed in its place.
+fn clear() {
+    eprint!("\r\x1b[K");
+}
+
+fn draw(count: usize, total: usize, started: Instant) {
+    let filled = (count * WIDTH).checked_div(total).unwrap_or(WIDTH);
+    let elapsed = started.elapsed().as_secs_f64();
+    let rate = if elapsed > 0.0 {
+        count as f64 / elapsed
+    } else {
+        0.0
+    };
+    let eta = if rate > 0.0 {
+        format!("{:.0}s", (total - count) as f64 / rate)
+    } else {
+        "?".to_string()
+    };
+
+    let mut stderr = std::io::stderr().lock();
+    let _ = write!(
+        stderr,
+        "\r\x1b[K[{}{}] {}/{} {:.1} files/s ETA {}",
+        "#".repeat(filled),
+        " ".repeat(WIDTH - filled),
+        count,
+        total,
+        rate,
+        eta
+    );
+    let _ = stderr.flush();
+}